@@ -1,209 +1,404 @@
-use crate::value::Value;
-
-#[derive(Debug, Clone, FromPrimitive, ToPrimitive)]
-pub enum OpCode {
-    OpConstant = 0,
-    OpNil = 1,
-    OpTrue = 2,
-    OpFalse = 3,
-    OpEqual = 4,
-    OpGreater = 5,
-    OpLess = 6,
-    OpAdd = 7,
-    OpSubtract = 8,
-    OpMultiply = 9,
-    OpDivide = 10,
-    OpNot = 11,
-    OpNegate = 12,
-    OpReturn = 13,
-    OpPrint = 14,
-    OpPop = 15,
-    OpDefineGlobal = 16,
-    OpSetGlobal = 17,
-    OpGetGlobal = 18,
-    OpGetLocal = 19,
-    OpSetLocal = 20,
-    OpJumpIfFalse = 21,
-    OpJump = 22,
-    OpLoop = 23,
-}
-
-pub struct Chunk {
-    pub code: Vec<u8>,
-    pub lines: Vec<usize>,
-    pub constants: Vec<Value>,
-}
-
-pub fn map_binary_to_opcode(instruction: u8) -> OpCode {
-    num::FromPrimitive::from_u8(instruction).unwrap()
-}
-
-pub fn map_opcode_to_binary(opcode: OpCode) -> u8 {
-    num::ToPrimitive::to_u8(&opcode).unwrap()
-}
-
-impl Chunk {
-    pub fn new() -> Chunk {
-        Chunk {
-            code: vec![],
-            lines: vec![],
-            constants: vec![],
-        }
-    }
-
-    pub fn count(&self) -> usize {
-        self.code.len()
-    }
-
-    pub fn write_chunk(&mut self, byte: u8, line: usize) {
-        self.code.push(byte);
-        self.lines.push(line);
-    }
-
-    // pub fn free_chunk(&mut self) {
-    //     self.code = vec!();
-    // }
-
-    pub fn add_constant(&mut self, value: Value) -> usize {
-        //println!("adding constant: {:?}", value);
-        self.constants.push(value);
-        return self.constants.len() - 1;
-    }
-
-    pub fn disassemble_chunk(&mut self) {
-        let mut offset = 0;
-        while offset < self.code.len() {
-            let instruction = self.code[offset];
-            offset = self.disassemble_instruction(instruction, offset);
-        }
-    }
-
-    fn disassemble_instruction(&self, instruction: u8, offset: usize) -> usize {
-        print!("{:#06x?} ", offset);
-
-        // if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
-        //     print!("   | ");
-        // } else {
-        //     print!("{} ", self.lines[offset]);
-        // }
-
-        let parsed_instruction = map_binary_to_opcode(instruction);
-        match parsed_instruction {
-            OpCode::OpReturn => {
-                println!("OpReturn");
-                offset + 1
-            }
-            OpCode::OpConstant => {
-                //let constant = self.constants[self.code[offset + 1] as usize].clone();
-                println!("OpConstant");
-                offset + 2
-            }
-            OpCode::OpNegate => {
-                println!("OpNegate");
-                offset + 1
-            }
-
-            OpCode::OpAdd => {
-                println!("OpAdd");
-                offset + 1
-            }
-            OpCode::OpSubtract => {
-                println!("OpSubtract");
-                offset + 1
-            }
-            OpCode::OpMultiply => {
-                println!("OpMultiply");
-                offset + 1
-            }
-            OpCode::OpDivide => {
-                println!("OpDivide");
-                offset + 1
-            }
-            OpCode::OpNil => {
-                println!("OpNil");
-                offset + 1
-            }
-            OpCode::OpTrue => {
-                println!("OpTrue");
-                offset + 1
-            }
-            OpCode::OpFalse => {
-                println!("OpFalse");
-                offset + 1
-            }
-            OpCode::OpNot => {
-                println!("OpNot");
-                offset + 1
-            }
-            OpCode::OpEqual => {
-                println!("OpEqual");
-                offset + 1
-            }
-            OpCode::OpGreater => {
-                println!("OpGreater");
-                offset + 1
-            }
-            OpCode::OpLess => {
-                println!("OpLess");
-                offset + 1
-            }
-            OpCode::OpPrint => {
-                println!("OpPrint");
-                offset + 1
-            }
-            OpCode::OpPop => {
-                println!("OpPop");
-                offset + 1
-            }
-            OpCode::OpDefineGlobal => {
-                println!("OpDefineGlobal");
-                offset + 2
-            }
-            OpCode::OpGetGlobal => {
-                println!("OpGetGlobal");
-                offset + 2
-            }
-            OpCode::OpSetGlobal => {
-                println!("OpSetGlobal");
-                offset + 2
-            }
-            OpCode::OpSetLocal => {
-                println!("OpSetLocal");
-                offset + 2
-            }
-            OpCode::OpGetLocal => {
-                println!("OpGetLocal");
-                offset + 2
-            },
-            OpCode::OpJump => {
-                println!("OpJump");
-                let a = (self.code[offset + 1] as u16) << 8;
-                let b = self.code[offset + 2] as u16;
-
-                let jump = a | b;
-                
-                offset + 3
-
-            }
-            OpCode::OpJumpIfFalse => {
-                println!("OpJumpIfFalse");
-                let a = (self.code[offset + 1] as u16) << 8;
-                let b = self.code[offset + 2] as u16;
-
-                let jump = a | b;
-                
-                offset + 3
-            }
-            OpCode::OpLoop => {
-                println!("OpLoop");
-                let a = (self.code[offset + 1] as u16) << 8;
-                let b = self.code[offset + 2] as u16;
-
-                let jump = a | b;
-                
-                offset + 3
-            }
-
-        }
-    }
-}
+#![allow(clippy::needless_return)]
+
+use crate::{scanner::Span, value::Value};
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, FromPrimitive, ToPrimitive)]
+pub enum OpCode {
+    OpConstant = 0,
+    OpNil = 1,
+    OpTrue = 2,
+    OpFalse = 3,
+    OpEqual = 4,
+    OpGreater = 5,
+    OpLess = 6,
+    OpAdd = 7,
+    OpSubtract = 8,
+    OpMultiply = 9,
+    OpDivide = 10,
+    OpNot = 11,
+    OpNegate = 12,
+    OpReturn = 13,
+    OpPrint = 14,
+    OpPop = 15,
+    OpDefineGlobal = 16,
+    OpSetGlobal = 17,
+    OpGetGlobal = 18,
+    OpGetLocal = 19,
+    OpSetLocal = 20,
+    OpJumpIfFalse = 21,
+    OpJump = 22,
+    OpLoop = 23,
+    OpBuildString = 24,
+    OpModulo = 25,
+    OpPower = 26,
+    OpFloorDivide = 27,
+    OpBitAnd = 28,
+    OpBitOr = 29,
+    OpBitXor = 30,
+    OpBitNot = 31,
+    OpShiftLeft = 32,
+    OpShiftRight = 33,
+    OpNotEqual = 34,
+    OpGreaterEqual = 35,
+    OpLessEqual = 36,
+    OpJumpIfNotNil = 37,
+    OpDefineConstGlobal = 38,
+    OpCall = 39,
+    OpBuildList = 40,
+    OpIndexGet = 41,
+    OpIndexSet = 42,
+    OpBuildMap = 43,
+    OpIterNext = 44,
+    OpRange = 45,
+    OpRangeInclusive = 46,
+    OpIn = 47,
+    OpGetProperty = 48,
+    OpInvoke = 49,
+    OpDupTwo = 50,
+    OpRotate = 51,
+    OpSwitchNumber = 52,
+    OpSwitchString = 53,
+}
+
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+}
+
+pub fn map_binary_to_opcode(instruction: u8) -> OpCode {
+    num::FromPrimitive::from_u8(instruction).unwrap()
+}
+
+pub fn map_opcode_to_binary(opcode: OpCode) -> u8 {
+    num::ToPrimitive::to_u8(&opcode).unwrap()
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk {
+            code: vec![],
+            spans: vec![],
+            constants: vec![],
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn write_chunk(&mut self, byte: u8, span: Span) {
+        self.code.push(byte);
+        self.spans.push(span);
+    }
+
+    // pub fn free_chunk(&mut self) {
+    //     self.code = vec!();
+    // }
+
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        //println!("adding constant: {:?}", value);
+        self.constants.push(value);
+        return self.constants.len() - 1;
+    }
+
+    #[allow(dead_code)]
+    pub fn disassemble_chunk(&mut self) {
+        let mut offset = 0;
+        while offset < self.code.len() {
+            let instruction = self.code[offset];
+            offset = self.disassemble_instruction(instruction, offset);
+        }
+    }
+
+    fn disassemble_instruction(&self, instruction: u8, offset: usize) -> usize {
+        print!("{:#06x?} ", offset);
+
+        let span = self.spans[offset];
+        if offset > 0 && span.line == self.spans[offset - 1].line {
+            print!("   |:{:<3} ", span.column);
+        } else {
+            print!("{:>4}:{:<3} ", span.line, span.column);
+        }
+
+        let parsed_instruction = map_binary_to_opcode(instruction);
+        match parsed_instruction {
+            OpCode::OpReturn => {
+                println!("OpReturn");
+                offset + 1
+            }
+            OpCode::OpConstant => {
+                //let constant = self.constants[self.code[offset + 1] as usize].clone();
+                println!("OpConstant");
+                offset + 2
+            }
+            OpCode::OpNegate => {
+                println!("OpNegate");
+                offset + 1
+            }
+
+            OpCode::OpAdd => {
+                println!("OpAdd");
+                offset + 1
+            }
+            OpCode::OpSubtract => {
+                println!("OpSubtract");
+                offset + 1
+            }
+            OpCode::OpMultiply => {
+                println!("OpMultiply");
+                offset + 1
+            }
+            OpCode::OpDivide => {
+                println!("OpDivide");
+                offset + 1
+            }
+            OpCode::OpNil => {
+                println!("OpNil");
+                offset + 1
+            }
+            OpCode::OpTrue => {
+                println!("OpTrue");
+                offset + 1
+            }
+            OpCode::OpFalse => {
+                println!("OpFalse");
+                offset + 1
+            }
+            OpCode::OpNot => {
+                println!("OpNot");
+                offset + 1
+            }
+            OpCode::OpEqual => {
+                println!("OpEqual");
+                offset + 1
+            }
+            OpCode::OpGreater => {
+                println!("OpGreater");
+                offset + 1
+            }
+            OpCode::OpLess => {
+                println!("OpLess");
+                offset + 1
+            }
+            OpCode::OpPrint => {
+                println!("OpPrint");
+                offset + 1
+            }
+            OpCode::OpPop => {
+                println!("OpPop");
+                offset + 1
+            }
+            OpCode::OpDefineGlobal => {
+                println!("OpDefineGlobal");
+                offset + 2
+            }
+            OpCode::OpDefineConstGlobal => {
+                println!("OpDefineConstGlobal");
+                offset + 2
+            }
+            OpCode::OpGetGlobal => {
+                println!("OpGetGlobal");
+                offset + 2
+            }
+            OpCode::OpSetGlobal => {
+                println!("OpSetGlobal");
+                offset + 2
+            }
+            OpCode::OpSetLocal => {
+                println!("OpSetLocal");
+                offset + 2
+            }
+            OpCode::OpGetLocal => {
+                println!("OpGetLocal");
+                offset + 2
+            }
+            OpCode::OpJump => {
+                let a = (self.code[offset + 1] as u16) << 8;
+                let b = self.code[offset + 2] as u16;
+
+                let jump = a | b;
+                println!("OpJump -> {:#06x?}", offset + 3 + jump as usize);
+
+                offset + 3
+            }
+            OpCode::OpJumpIfFalse => {
+                let a = (self.code[offset + 1] as u16) << 8;
+                let b = self.code[offset + 2] as u16;
+
+                let jump = a | b;
+                println!("OpJumpIfFalse -> {:#06x?}", offset + 3 + jump as usize);
+
+                offset + 3
+            }
+            OpCode::OpJumpIfNotNil => {
+                let a = (self.code[offset + 1] as u16) << 8;
+                let b = self.code[offset + 2] as u16;
+
+                let jump = a | b;
+                println!("OpJumpIfNotNil -> {:#06x?}", offset + 3 + jump as usize);
+
+                offset + 3
+            }
+            OpCode::OpLoop => {
+                let a = (self.code[offset + 1] as u16) << 8;
+                let b = self.code[offset + 2] as u16;
+
+                let jump = a | b;
+                println!("OpLoop -> {:#06x?}", offset + 3 - jump as usize);
+
+                offset + 3
+            }
+            OpCode::OpModulo => {
+                println!("OpModulo");
+                offset + 1
+            }
+            OpCode::OpPower => {
+                println!("OpPower");
+                offset + 1
+            }
+            OpCode::OpFloorDivide => {
+                println!("OpFloorDivide");
+                offset + 1
+            }
+            OpCode::OpBitAnd => {
+                println!("OpBitAnd");
+                offset + 1
+            }
+            OpCode::OpBitOr => {
+                println!("OpBitOr");
+                offset + 1
+            }
+            OpCode::OpBitXor => {
+                println!("OpBitXor");
+                offset + 1
+            }
+            OpCode::OpBitNot => {
+                println!("OpBitNot");
+                offset + 1
+            }
+            OpCode::OpShiftLeft => {
+                println!("OpShiftLeft");
+                offset + 1
+            }
+            OpCode::OpShiftRight => {
+                println!("OpShiftRight");
+                offset + 1
+            }
+            OpCode::OpNotEqual => {
+                println!("OpNotEqual");
+                offset + 1
+            }
+            OpCode::OpGreaterEqual => {
+                println!("OpGreaterEqual");
+                offset + 1
+            }
+            OpCode::OpLessEqual => {
+                println!("OpLessEqual");
+                offset + 1
+            }
+            OpCode::OpBuildString => {
+                println!("OpBuildString {}", self.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::OpCall => {
+                println!("OpCall {}", self.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::OpBuildList => {
+                println!("OpBuildList {}", self.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::OpIterNext => {
+                let a = (self.code[offset + 2] as u16) << 8;
+                let b = self.code[offset + 3] as u16;
+
+                let jump = a | b;
+                println!(
+                    "OpIterNext {} -> {:#06x?}",
+                    self.code[offset + 1],
+                    offset + 4 + jump as usize
+                );
+
+                offset + 4
+            }
+            OpCode::OpBuildMap => {
+                println!("OpBuildMap {}", self.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::OpRange => {
+                println!("OpRange");
+                offset + 1
+            }
+            OpCode::OpRangeInclusive => {
+                println!("OpRangeInclusive");
+                offset + 1
+            }
+            OpCode::OpGetProperty => {
+                println!("OpGetProperty {}", self.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::OpInvoke => {
+                println!(
+                    "OpInvoke {} ({} args)",
+                    self.code[offset + 1],
+                    self.code[offset + 2]
+                );
+                offset + 3
+            }
+            OpCode::OpIn => {
+                println!("OpIn");
+                offset + 1
+            }
+            OpCode::OpIndexGet => {
+                println!("OpIndexGet");
+                offset + 1
+            }
+            OpCode::OpIndexSet => {
+                println!("OpIndexSet");
+                offset + 1
+            }
+            OpCode::OpDupTwo => {
+                println!("OpDupTwo");
+                offset + 1
+            }
+            OpCode::OpRotate => {
+                println!("OpRotate");
+                offset + 1
+            }
+            OpCode::OpSwitchNumber => {
+                let count = self.code[offset + 2] as usize;
+                let end = offset + 3 + 2 * count + 2;
+                println!("OpSwitchNumber from constant {}", self.code[offset + 1]);
+                for i in 0..=count {
+                    let entry = offset + 3 + 2 * i;
+                    let jump = (self.code[entry] as usize) << 8 | self.code[entry + 1] as usize;
+                    if i < count {
+                        println!("{:>20} -> {:#06x?}", i, end + jump);
+                    } else {
+                        println!("{:>20} -> {:#06x?}", "default", end + jump);
+                    }
+                }
+                end
+            }
+            OpCode::OpSwitchString => {
+                let count = self.code[offset + 1] as usize;
+                let end = offset + 2 + 3 * count + 2;
+                println!("OpSwitchString");
+                for i in 0..=count {
+                    let entry = offset + 2 + 3 * i;
+                    if i < count {
+                        let jump = (self.code[entry + 1] as usize) << 8 | self.code[entry + 2] as usize;
+                        println!("{:>20} -> {:#06x?}", self.code[entry], end + jump);
+                    } else {
+                        let jump = (self.code[entry] as usize) << 8 | self.code[entry + 1] as usize;
+                        println!("{:>20} -> {:#06x?}", "default", end + jump);
+                    }
+                }
+                end
+            }
+
+        }
+    }
+}
@@ -1,769 +1,1712 @@
-use crate::{
-    chunk::{map_opcode_to_binary, Chunk, OpCode},
-    scanner::{Scanner, Token, TokenType},
-    value::Value,
-    vm::VM,
-};
-
-pub struct Parser {
-    current: Token,
-    previous: Token,
-    had_error: bool,
-    panic_mode: bool,
-    scanner: Scanner,
-    compiling_chunk: Chunk,
-    compiler: Compiler,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-enum Precedence {
-    PrecNone,
-    PrecAssignment, // =
-    PrecOr,         // Or
-    PrecAnd,        // And
-    PrecEquality,   // == !=
-    PrecComparison, // < > <= >=
-    PrecTerm,       // + -
-    PrecFactor,     // * /
-    PrecUnary,      // ! -
-    PrecCall,       // . ()
-    PrecPrimary,
-}
-
-fn get_next_rule(precedence: Precedence) -> Precedence {
-    match precedence {
-        Precedence::PrecNone => Precedence::PrecAssignment,
-        Precedence::PrecAssignment => Precedence::PrecOr,
-        Precedence::PrecOr => Precedence::PrecAnd,
-        Precedence::PrecAnd => Precedence::PrecEquality,
-        Precedence::PrecEquality => Precedence::PrecComparison,
-        Precedence::PrecComparison => Precedence::PrecTerm,
-        Precedence::PrecTerm => Precedence::PrecFactor,
-        Precedence::PrecFactor => Precedence::PrecUnary,
-        Precedence::PrecUnary => Precedence::PrecCall,
-        Precedence::PrecCall => Precedence::PrecPrimary,
-        Precedence::PrecPrimary => Precedence::PrecPrimary,
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum ParseFn {
-    Grouping,
-    Unary,
-    Binary,
-    Number,
-    None,
-    Literal,
-    String,
-    Variable,
-    And,
-    Or,
-}
-
-#[derive(Debug)]
-struct ParseRule {
-    prefix: ParseFn,
-    infix: ParseFn,
-    precedence: Precedence,
-}
-
-const UINT8_COUNT: usize = (u8::MAX as usize) + 1;
-const DEFAULT_TOKEN: Token = Token::new_dummy_token();
-const DEFAULT_LOCAL: Local = Local {
-    depth: 0,
-    name: DEFAULT_TOKEN,
-};
-
-struct Compiler {
-    local_count: isize,
-    scope_depth: isize,
-    locals: [Local; UINT8_COUNT],
-}
-
-impl Compiler {
-    fn new() -> Compiler {
-        let array = [DEFAULT_LOCAL; UINT8_COUNT];
-        Compiler {
-            local_count: 0,
-            scope_depth: 0,
-            locals: array,
-        }
-    }
-}
-
-struct Local {
-    name: Token,
-    depth: isize,
-}
-
-impl Default for Local {
-    fn default() -> Self {
-        Local {
-            depth: 0,
-            name: Token::new_dummy_token(),
-        }
-    }
-}
-
-impl Parser {
-    pub fn get_compiling_chunk(self) -> Chunk {
-        self.compiling_chunk
-    }
-
-    pub fn new(source: &str) -> Parser {
-        Parser {
-            current: Token::new_dummy_token(),
-            previous: Token::new_dummy_token(),
-            had_error: false,
-            panic_mode: false,
-            scanner: Scanner::new(source),
-            compiling_chunk: Chunk::new(),
-            compiler: Compiler::new(),
-        }
-    }
-
-    fn get_rule(operator_type: TokenType) -> ParseRule {
-        let (prefix, infix, precedence) = Parser::get_rule_tuple(operator_type);
-
-        ParseRule {
-            prefix: prefix,
-            infix: infix,
-            precedence: precedence,
-        }
-    }
-
-    fn get_rule_tuple(operator_type: TokenType) -> (ParseFn, ParseFn, Precedence) {
-        match operator_type {
-            TokenType::TokenLeftParen => (ParseFn::Grouping, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenRightParen => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenLeftBrace => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenRightBrace => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenComma => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenDot => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenMinus => (ParseFn::Unary, ParseFn::Binary, Precedence::PrecTerm),
-            TokenType::TokenPlus => (ParseFn::None, ParseFn::Binary, Precedence::PrecTerm),
-            TokenType::TokenSemicolon => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenSlash => (ParseFn::None, ParseFn::Binary, Precedence::PrecFactor),
-            TokenType::TokenStar => (ParseFn::None, ParseFn::Binary, Precedence::PrecFactor),
-            TokenType::TokenBang => (ParseFn::Unary, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenBangEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecEquality),
-            TokenType::TokenEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenEqualEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecEquality),
-            TokenType::TokenGreater => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
-            TokenType::TokenGreaterEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
-            TokenType::TokenLess => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
-            TokenType::TokenLessEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
-            TokenType::TokenIdentifier => (ParseFn::Variable, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenString => (ParseFn::String, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenNumber => (ParseFn::Number, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenAnd => (ParseFn::None, ParseFn::And, Precedence::PrecAnd),
-            TokenType::TokenClass => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenElse => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenFalse => (ParseFn::Literal, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenFor => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenFun => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenIf => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenNil => (ParseFn::Literal, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenOr => (ParseFn::None, ParseFn::Or, Precedence::PrecOr),
-            TokenType::TokenPrint => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenReturn => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenSuper => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenThis => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenTrue => (ParseFn::Literal, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenVar => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenWhile => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenError => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-            TokenType::TokenEof => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
-        }
-    }
-
-    fn error(&mut self, message: &str) {
-        self.error_at(&self.previous.clone(), message);
-    }
-
-    fn error_at(&mut self, token: &Token, message: &str) {
-        if self.panic_mode {
-            return;
-        }
-        self.panic_mode = true;
-        print!("[line {} Error", token.line);
-
-        if token.token_type == TokenType::TokenEof {
-            print!(" at end");
-        } else if token.token_type == TokenType::TokenError {
-        } else {
-            print!(" at {}", token.content)
-        }
-
-        println!(": {}", message);
-        self.had_error = true;
-    }
-
-    fn advance(&mut self) {
-        self.previous = self.current.clone();
-
-        loop {
-            self.current = self.scanner.scan_token();
-            //println!("token read is {:?}", &self.current);
-
-            if self.current.token_type != TokenType::TokenError {
-                break;
-            }
-
-            self.error_at_current(&self.current.content.clone());
-        }
-    }
-
-    fn error_at_current(&mut self, message: &str) {
-        self.error_at(&self.current.clone(), message);
-    }
-
-    pub fn compile(&mut self, vm: &mut VM) -> bool {
-        self.advance();
-
-        while !self.match_token(TokenType::TokenEof) {
-            self.declaration(vm);
-        }
-
-        self.end_compiler(vm);
-        return !self.had_error;
-    }
-
-    fn declaration(&mut self, vm: &mut VM) {
-        if self.match_token(TokenType::TokenVar) {
-            self.var_declaration(vm);
-        } else {
-            self.statement(vm);
-        }
-
-        if self.panic_mode {
-            self.synchronize(vm);
-        }
-    }
-
-    fn var_declaration(&mut self, vm: &mut VM) {
-        let global = self.parse_variable("Expect variable name.", vm);
-
-        if self.match_token(TokenType::TokenEqual) {
-            self.expression(vm);
-        } else {
-            self.emit_byte(map_opcode_to_binary(OpCode::OpNil));
-        }
-
-        self.consume(
-            TokenType::TokenSemicolon,
-            "Expect ';' after variable declaration.",
-        );
-
-        self.define_variable(global);
-    }
-
-    fn parse_variable(&mut self, message: &str, vm: &mut VM) -> u8 {
-        self.consume(TokenType::TokenIdentifier, message);
-        self.declare_variable(vm);
-
-        if self.compiler.scope_depth > 0 {
-            return 0;
-        }
-
-        return self.identifier_constant(&self.previous.clone(), vm);
-    }
-
-    fn define_variable(&mut self, global: u8) {
-        if self.compiler.scope_depth > 0 {
-            self.mark_initialized();
-            return;
-        }
-
-        self.emit_bytes(map_opcode_to_binary(OpCode::OpDefineGlobal), global);
-    }
-
-    fn mark_initialized(&mut self) {
-        self.compiler.locals[(self.compiler.local_count - 1) as usize].depth =
-            self.compiler.scope_depth;
-    }
-
-    fn identifier_constant(&mut self, token: &Token, vm: &mut VM) -> u8 {
-        let obj = vm.get_or_create_string_object(&token.content);
-        return self.make_constant(obj);
-    }
-
-    fn declare_variable(&mut self, vm: &mut VM) {
-        if self.compiler.scope_depth == 0 {
-            return;
-        }
-
-        let name = self.previous.clone();
-        let mut i = self.compiler.local_count - 1;
-
-        while i >= 0 {
-            let local = &self.compiler.locals[i as usize];
-
-            if local.depth != -1isize && local.depth < self.compiler.scope_depth {
-                break;
-            }
-
-            if Parser::identifiers_equal(&name, &local.name) {
-                self.error("Already a variable with this name in this scope.");
-            }
-
-            i = i - 1;
-        }
-
-        self.add_local(name, vm);
-    }
-
-    fn identifiers_equal(a: &Token, b: &Token) -> bool {
-        a.content == b.content
-    }
-
-    fn add_local(&mut self, name: Token, _: &mut VM) {
-        if self.compiler.local_count == UINT8_COUNT as isize {
-            self.error("Too many local variables in function.");
-            return;
-        }
-
-        let local = &mut self.compiler.locals[self.compiler.local_count as usize];
-        self.compiler.local_count = self.compiler.local_count + 1;
-
-        local.name = name;
-        local.depth = -1;
-    }
-
-    fn synchronize(&mut self, _: &mut VM) {
-        self.panic_mode = false;
-
-        while self.current.token_type != TokenType::TokenEof {
-            if self.previous.token_type == TokenType::TokenSemicolon {
-                return;
-            }
-
-            match self.current.token_type {
-                TokenType::TokenClass => return,
-                TokenType::TokenFun => return,
-                TokenType::TokenVar => return,
-                TokenType::TokenFor => return,
-                TokenType::TokenIf => return,
-                TokenType::TokenWhile => return,
-                TokenType::TokenPrint => return,
-                TokenType::TokenReturn => return,
-                _ => {}
-            }
-        }
-    }
-
-    fn statement(&mut self, vm: &mut VM) {
-        if self.match_token(TokenType::TokenPrint) {
-            self.print_statement(vm);
-        } else if self.match_token(TokenType::TokenFor) {
-            self.for_statement(vm);
-        } else if self.match_token(TokenType::TokenIf) {
-            self.if_statement(vm);
-        } else if self.match_token(TokenType::TokenWhile) {
-            self.while_statement(vm);
-        } else if self.match_token(TokenType::TokenLeftBrace) {
-            self.begin_scope();
-            self.block(vm);
-            self.end_scope();
-        } else {
-            self.expression_statement(vm);
-        }
-    }
-
-    fn for_statement(&mut self, vm: &mut VM) {
-        self.begin_scope();
-        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'for'.");
-        if self.match_token(TokenType::TokenSemicolon) {
-            // No initializer.
-        } else if self.match_token(TokenType::TokenVar) {
-            self.var_declaration(vm);
-        } else {
-            self.expression_statement(vm);
-        }
-        
-        let mut loop_start = self.current_chunk().count();
-        let mut exit_jump: Option<usize> = None;
-        if !self.match_token(TokenType::TokenSemicolon) {
-            self.expression(vm);
-            self.consume(TokenType::TokenSemicolon, "Expect ';' after loop condition.");
-
-            // Jump out of the loop if the condition is false.
-            exit_jump = Some(self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse)));
-            self.emit_byte(map_opcode_to_binary(OpCode::OpPop)); // Condition.
-        }
-
-        if !self.match_token(TokenType::TokenRightParen) {
-            let body_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
-            let increment_start = self.current_chunk().count();
-            self.expression(vm);
-            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-            self.consume(TokenType::TokenRightParen, "Expect ')' after for clauses.");
-            self.emit_loop(loop_start);
-            loop_start = increment_start;
-            self.patch_jump(body_jump);
-
-        }
-
-        self.statement(vm);
-        self.emit_loop(loop_start);
-
-        if exit_jump.is_some() {
-            self.patch_jump(exit_jump.unwrap());
-            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-        }
-        self.end_scope();
-    }
-
-    fn while_statement(&mut self, vm: &mut VM) {
-        let loop_start = self.current_chunk().count();
-        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'while'.");
-        self.expression(vm);
-        self.consume(TokenType::TokenRightParen, "Expect ')' after condition.");
-
-        let exit_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-        self.statement(vm);
-        self.emit_loop(loop_start);
-        self.patch_jump(exit_jump);
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-    }
-
-    fn emit_loop(&mut self, loop_start: usize) {
-        self.emit_byte(map_opcode_to_binary(OpCode::OpLoop));
-        let offset = self.current_chunk().count() - loop_start + 2;
-        if offset > u16::MAX as usize {
-            self.error("Loop body too larger");
-        }
-
-        self.emit_byte(((offset >> 8) & 0xFF) as u8);
-        self.emit_byte((offset & 0xFF) as u8);
-    }
-
-    fn if_statement(&mut self, vm: &mut VM) {
-        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'if'.");
-        self.expression(vm);
-        self.consume(TokenType::TokenRightParen, "Expect ')' after condition.");
-
-        let then_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-        self.statement(vm);
-        let else_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
-        self.patch_jump(then_jump);
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-
-        if self.match_token(TokenType::TokenElse) {
-            self.statement(vm);
-        }
-        self.patch_jump(else_jump);
-    }
-
-    fn emit_jump(&mut self, instruction: u8) -> usize {
-        self.emit_byte(instruction);
-        self.emit_byte(0xFF);
-        self.emit_byte(0xFF);
-        self.current_chunk().count() - 2
-    }
-
-    fn patch_jump(&mut self, offset: usize) {
-        let jump = self.current_chunk().count() - offset - 2;
-        if jump > u16::MAX as usize {
-            self.error("Too much code to jump over.");
-        }
-
-        self.current_chunk().code[offset] = ((jump >> 8) & 0xFF) as u8;
-        self.current_chunk().code[offset + 1] = (jump & 0xFF) as u8;
-    }
-
-    fn block(&mut self, vm: &mut VM) {
-        while !self.check(TokenType::TokenRightBrace) && !self.check(TokenType::TokenEof) {
-            self.declaration(vm);
-        }
-
-        self.consume(TokenType::TokenRightBrace, "Expect '}' after block.");
-    }
-
-    fn begin_scope(&mut self) {
-        self.compiler.scope_depth = self.compiler.scope_depth + 1;
-    }
-
-    fn end_scope(&mut self) {
-        self.compiler.scope_depth = self.compiler.scope_depth - 1;
-
-        while self.compiler.local_count > 0
-            && self.compiler.locals[(self.compiler.local_count - 1) as usize].depth
-                > self.compiler.scope_depth
-        {
-            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-            self.compiler.local_count = self.compiler.local_count - 1;
-        }
-    }
-
-    fn expression_statement(&mut self, vm: &mut VM) {
-        self.expression(vm);
-        self.consume(TokenType::TokenSemicolon, "Expect ';' after expression.");
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop))
-    }
-
-    fn match_token(&mut self, token_type: TokenType) -> bool {
-        if !self.check(token_type) {
-            return false;
-        }
-        self.advance();
-        true
-    }
-
-    fn check(&mut self, token_type: TokenType) -> bool {
-        self.current.token_type == token_type
-    }
-
-    fn print_statement(&mut self, vm: &mut VM) {
-        self.expression(vm);
-        self.consume(TokenType::TokenSemicolon, "Expect ';' after value.");
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPrint));
-    }
-
-    fn consume(&mut self, token_type: TokenType, message: &str) {
-        if self.current.token_type == token_type {
-            self.advance();
-            return;
-        }
-
-        //println!("consume failed. Expected {:?}, got {:?}", token_type, self.current);
-
-        self.error_at_current(message);
-    }
-
-    fn emit_byte(&mut self, byte: u8) {
-        let line = self.previous.line;
-        self.current_chunk().write_chunk(byte, line);
-    }
-
-    fn current_chunk(&mut self) -> &mut Chunk {
-        &mut self.compiling_chunk
-    }
-
-    fn emit_bytes(&mut self, byte1: u8, byte2: u8) {
-        self.emit_byte(byte1);
-        self.emit_byte(byte2);
-    }
-
-    fn end_compiler(&mut self, vm: &mut VM) {
-        self.emit_return(vm);
-    }
-
-    fn emit_return(&mut self, _: &mut VM) {
-        self.emit_byte(map_opcode_to_binary(OpCode::OpReturn));
-    }
-
-    fn expression(&mut self, vm: &mut VM) {
-        self.parse_precedence(Precedence::PrecAssignment, vm);
-    }
-
-    fn number(&mut self, _: &mut VM) {
-        let value: f64 = self.previous.content.parse().unwrap();
-        self.emit_constant(Value::Number(value));
-    }
-
-    fn emit_constant(&mut self, value: Value) {
-        let constant = self.make_constant(value);
-        self.emit_bytes(map_opcode_to_binary(OpCode::OpConstant), constant);
-    }
-
-    pub fn make_constant(&mut self, value: Value) -> u8 {
-        let constant = self.current_chunk().add_constant(value);
-        if constant > (u8::MAX) as usize {
-            self.error("Too many constants in one chunk.");
-            return 0u8;
-        }
-
-        return constant as u8;
-    }
-
-    fn grouping(&mut self, vm: &mut VM) {
-        self.expression(vm);
-        self.consume(TokenType::TokenRightParen, "Expect ')' after expression.");
-    }
-
-    fn unary(&mut self, vm: &mut VM) {
-        let operator_type: TokenType = self.previous.token_type;
-
-        self.parse_precedence(Precedence::PrecUnary, vm);
-
-        match operator_type {
-            TokenType::TokenBang => self.emit_byte(map_opcode_to_binary(OpCode::OpNot)),
-            TokenType::TokenMinus => self.emit_byte(map_opcode_to_binary(OpCode::OpNegate)),
-            _ => {}
-        }
-    }
-
-    fn parse_precedence(&mut self, precedence: Precedence, vm: &mut VM) {
-        self.advance();
-        let rule = Parser::get_rule(self.previous.token_type);
-
-        if rule.prefix == ParseFn::None {
-            self.error("Expect expression.");
-            return;
-        }
-
-        let can_assign = precedence <= Precedence::PrecAssignment;
-        self.run_rule(rule.prefix, can_assign, vm);
-
-        while precedence <= Parser::get_rule(self.current.token_type).precedence {
-            self.advance();
-            let new_rule = Parser::get_rule(self.previous.token_type);
-            let infix = new_rule.infix;
-            self.run_rule(infix, can_assign, vm);
-        }
-
-        if can_assign && self.match_token(TokenType::TokenEqual) {
-            self.error("Invalid assignment target.");
-        }
-    }
-
-    fn string(&mut self, vm: &mut VM) {
-        let mut previous_str = self.previous.content.clone();
-        previous_str.remove(0);
-        previous_str.remove(previous_str.len() - 1);
-
-        let value = vm.get_or_create_string_object(&previous_str);
-
-        self.emit_constant(value);
-    }
-
-    fn variable(&mut self, can_assign: bool, vm: &mut VM) {
-        self.named_variable(self.previous.clone(), can_assign, vm);
-    }
-
-    fn named_variable(&mut self, name: Token, can_assign: bool, vm: &mut VM) {
-        let mut arg = self.resolve_local(&name, vm);
-
-        let (get_op, set_op) = if arg != -1 {
-            (OpCode::OpGetLocal, OpCode::OpSetLocal)
-        } else {
-            arg = self.identifier_constant(&name, vm) as isize;
-            (OpCode::OpGetGlobal, OpCode::OpSetGlobal)
-        };
-
-        if can_assign && self.match_token(TokenType::TokenEqual) {
-            self.expression(vm);
-            self.emit_bytes(map_opcode_to_binary(set_op), arg as u8);
-        } else {
-            self.emit_bytes(map_opcode_to_binary(get_op), arg as u8);
-        }
-    }
-
-    fn resolve_local(&mut self, name: &Token, _: &mut VM) -> isize {
-        let mut i = self.compiler.local_count - 1;
-
-        while i >= 0 {
-            let local = &self.compiler.locals[i as usize];
-            if Parser::identifiers_equal(name, &local.name) {
-                if local.depth == -1 {
-                    self.error("Can't read local variable in its own initializer.");
-                }
-                return i;
-            }
-            i = i - 1;
-        }
-
-        -1
-    }
-
-    fn literal(&mut self, _: &mut VM) {
-        match self.previous.token_type {
-            TokenType::TokenFalse => {
-                self.emit_byte(map_opcode_to_binary(OpCode::OpFalse));
-            }
-            TokenType::TokenNil => {
-                self.emit_byte(map_opcode_to_binary(OpCode::OpNil));
-            }
-            TokenType::TokenTrue => {
-                self.emit_byte(map_opcode_to_binary(OpCode::OpTrue));
-            }
-            _ => {}
-        }
-    }
-
-    fn and(&mut self, vm: &mut VM) {
-        let end_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-        self.parse_precedence(Precedence::PrecAnd, vm);
-        self.patch_jump(end_jump);
-    }
-
-    fn or(&mut self, vm: &mut VM) {
-        let else_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
-        let end_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
-
-        self.patch_jump(else_jump);
-        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
-        self.parse_precedence(Precedence::PrecOr, vm);
-        self.patch_jump(end_jump);
-    }
-
-    fn run_rule(&mut self, rule: ParseFn, can_assign: bool, vm: &mut VM) {
-        match rule {
-            ParseFn::Binary => {
-                self.binary(vm);
-            }
-            ParseFn::Grouping => {
-                self.grouping(vm);
-            }
-            ParseFn::Number => {
-                self.number(vm);
-            }
-            ParseFn::Unary => {
-                self.unary(vm);
-            }
-            ParseFn::Literal => {
-                self.literal(vm);
-            }
-            ParseFn::String => {
-                self.string(vm);
-            }
-            ParseFn::Variable => {
-                self.variable(can_assign, vm);
-            }
-            ParseFn::None => {
-                panic!();
-            }
-            ParseFn::And => {
-                self.and(vm);
-            }
-            ParseFn::Or => {
-                self.or(vm);
-            }
-        }
-    }
-
-    fn binary(&mut self, vm: &mut VM) {
-        let operator_type: TokenType = self.previous.token_type;
-
-        let rule = Parser::get_rule(operator_type);
-
-        self.parse_precedence(get_next_rule(rule.precedence), vm);
-
-        match operator_type {
-            TokenType::TokenBangEqual => self.emit_bytes(
-                map_opcode_to_binary(OpCode::OpEqual),
-                map_opcode_to_binary(OpCode::OpNot),
-            ),
-            TokenType::TokenEqualEqual => self.emit_byte(map_opcode_to_binary(OpCode::OpEqual)),
-            TokenType::TokenGreater => self.emit_byte(map_opcode_to_binary(OpCode::OpGreater)),
-            TokenType::TokenGreaterEqual => self.emit_bytes(
-                map_opcode_to_binary(OpCode::OpLess),
-                map_opcode_to_binary(OpCode::OpNot),
-            ),
-            TokenType::TokenLess => self.emit_byte(map_opcode_to_binary(OpCode::OpLess)),
-            TokenType::TokenLessEqual => self.emit_bytes(
-                map_opcode_to_binary(OpCode::OpGreater),
-                map_opcode_to_binary(OpCode::OpNot),
-            ),
-            TokenType::TokenPlus => self.emit_byte(map_opcode_to_binary(OpCode::OpAdd)),
-            TokenType::TokenMinus => self.emit_byte(map_opcode_to_binary(OpCode::OpSubtract)),
-            TokenType::TokenStar => self.emit_byte(map_opcode_to_binary(OpCode::OpMultiply)),
-            TokenType::TokenSlash => self.emit_byte(map_opcode_to_binary(OpCode::OpDivide)),
-            _ => {}
-        }
-    }
-}
+#![allow(
+    clippy::needless_return,
+    clippy::assign_op_pattern,
+    clippy::redundant_field_names
+)]
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    chunk::{map_opcode_to_binary, Chunk, OpCode},
+    diagnostic::{Diagnostic, Severity},
+    scanner::{Scanner, Span, Token, TokenType},
+    value::{values_equal, Value},
+    vm::VM,
+};
+
+pub struct Parser {
+    current: Token,
+    previous: Token,
+    panic_mode: bool,
+    diagnostics: Vec<Diagnostic>,
+    scanner: Scanner,
+    compiling_chunk: Chunk,
+    compiler: Compiler,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    PrecNone,
+    PrecAssignment,  // =
+    PrecConditional, // ?:
+    PrecCoalesce,    // ??
+    PrecOr,          // Or
+    PrecAnd,         // And
+    PrecEquality,    // == !=
+    PrecComparison,  // < > <= >= in
+    PrecRange,       // .. ..=
+    PrecBitOr,       // |
+    PrecBitXor,      // ^
+    PrecBitAnd,      // &
+    PrecShift,       // << >>
+    PrecTerm,        // + -
+    PrecFactor,      // * / % ~/
+    PrecUnary,       // ! - ~
+    PrecExponent,    // **
+    PrecCall,        // . ()
+    PrecPrimary,
+}
+
+fn get_next_rule(precedence: Precedence) -> Precedence {
+    match precedence {
+        Precedence::PrecNone => Precedence::PrecAssignment,
+        Precedence::PrecAssignment => Precedence::PrecConditional,
+        Precedence::PrecConditional => Precedence::PrecCoalesce,
+        Precedence::PrecCoalesce => Precedence::PrecOr,
+        Precedence::PrecOr => Precedence::PrecAnd,
+        Precedence::PrecAnd => Precedence::PrecEquality,
+        Precedence::PrecEquality => Precedence::PrecComparison,
+        Precedence::PrecComparison => Precedence::PrecRange,
+        Precedence::PrecRange => Precedence::PrecBitOr,
+        Precedence::PrecBitOr => Precedence::PrecBitXor,
+        Precedence::PrecBitXor => Precedence::PrecBitAnd,
+        Precedence::PrecBitAnd => Precedence::PrecShift,
+        Precedence::PrecShift => Precedence::PrecTerm,
+        Precedence::PrecTerm => Precedence::PrecFactor,
+        Precedence::PrecFactor => Precedence::PrecUnary,
+        Precedence::PrecUnary => Precedence::PrecExponent,
+        Precedence::PrecExponent => Precedence::PrecCall,
+        Precedence::PrecCall => Precedence::PrecPrimary,
+        Precedence::PrecPrimary => Precedence::PrecPrimary,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseFn {
+    Grouping,
+    Unary,
+    Binary,
+    Number,
+    None,
+    Literal,
+    String,
+    Variable,
+    And,
+    Or,
+    Interpolation,
+    Increment,
+    Conditional,
+    Coalesce,
+    Call,
+    List,
+    Map,
+    Index,
+    Dot,
+}
+
+#[derive(Debug)]
+struct ParseRule {
+    prefix: ParseFn,
+    infix: ParseFn,
+    precedence: Precedence,
+}
+
+const UINT8_COUNT: usize = (u8::MAX as usize) + 1;
+const DEFAULT_TOKEN: Token = Token::new_dummy_token();
+const DEFAULT_LOCAL: Local = Local {
+    depth: 0,
+    name: DEFAULT_TOKEN,
+    constant: false,
+};
+
+struct Compiler {
+    local_count: isize,
+    scope_depth: isize,
+    locals: [Local; UINT8_COUNT],
+    loops: Vec<Loop>,
+    // Globals declared with `const`, with their value when the initializer
+    // is a literal so reads can be inlined.
+    constant_globals: HashMap<String, Option<Value>>,
+    // Every global declared with `var` or `const`, and the names of the
+    // assignments to globals, for the undeclared global warning.
+    declared_globals: HashSet<String>,
+    global_assignments: Vec<Token>,
+}
+
+impl Compiler {
+    fn new() -> Compiler {
+        let array = [DEFAULT_LOCAL; UINT8_COUNT];
+        Compiler {
+            local_count: 0,
+            scope_depth: 0,
+            locals: array,
+            loops: vec![],
+            constant_globals: HashMap::new(),
+            declared_globals: HashSet::new(),
+            global_assignments: vec![],
+        }
+    }
+}
+
+// The innermost loops being compiled, for `break` and `continue`.
+struct Loop {
+    // Where `continue` jumps to: the condition of a `while`, the increment
+    // clause of a `for`.
+    start: usize,
+    // Locals declared deeper than this belong to the loop body and are
+    // popped before jumping out of it.
+    scope_depth: isize,
+    break_jumps: Vec<usize>,
+}
+
+// The jump table of a switch being compiled. Each entry's offset is filled
+// in once the whole switch is compiled; entries without a case go to the
+// default case, or past the switch if there is none.
+struct SwitchTable {
+    values: Vec<Value>,
+    entries: Vec<usize>,
+    targets: Vec<Option<usize>>,
+    default_entry: usize,
+    default_target: Option<usize>,
+    // Offsets are relative to the end of the instruction.
+    end: usize,
+}
+
+struct Local {
+    name: Token,
+    depth: isize,
+    constant: bool,
+}
+
+impl Default for Local {
+    fn default() -> Self {
+        Local {
+            depth: 0,
+            name: Token::new_dummy_token(),
+            constant: false,
+        }
+    }
+}
+
+impl Parser {
+    pub fn get_compiling_chunk(self) -> Chunk {
+        self.compiling_chunk
+    }
+
+    pub fn new(source: &str) -> Parser {
+        Parser {
+            current: Token::new_dummy_token(),
+            previous: Token::new_dummy_token(),
+            panic_mode: false,
+            diagnostics: vec![],
+            scanner: Scanner::new(source),
+            compiling_chunk: Chunk::new(),
+            compiler: Compiler::new(),
+        }
+    }
+
+    fn get_rule(operator_type: TokenType) -> ParseRule {
+        let (prefix, infix, precedence) = Parser::get_rule_tuple(operator_type);
+
+        ParseRule {
+            prefix: prefix,
+            infix: infix,
+            precedence: precedence,
+        }
+    }
+
+    fn get_rule_tuple(operator_type: TokenType) -> (ParseFn, ParseFn, Precedence) {
+        match operator_type {
+            TokenType::TokenLeftParen => (ParseFn::Grouping, ParseFn::Call, Precedence::PrecCall),
+            TokenType::TokenRightParen => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenLeftBrace => (ParseFn::Map, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenRightBrace => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenLeftBracket => (ParseFn::List, ParseFn::Index, Precedence::PrecCall),
+            TokenType::TokenRightBracket => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenComma => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenDot => (ParseFn::None, ParseFn::Dot, Precedence::PrecCall),
+            TokenType::TokenDotDot => (ParseFn::None, ParseFn::Binary, Precedence::PrecRange),
+            TokenType::TokenDotDotEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecRange),
+            TokenType::TokenMinus => (ParseFn::Unary, ParseFn::Binary, Precedence::PrecTerm),
+            TokenType::TokenMinusEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenMinusMinus => (ParseFn::Increment, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenPlus => (ParseFn::None, ParseFn::Binary, Precedence::PrecTerm),
+            TokenType::TokenPlusEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenPlusPlus => (ParseFn::Increment, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenSemicolon => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenColon => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenQuestion => (ParseFn::None, ParseFn::Conditional, Precedence::PrecConditional),
+            TokenType::TokenQuestionQuestion => (ParseFn::None, ParseFn::Coalesce, Precedence::PrecCoalesce),
+            TokenType::TokenSlash => (ParseFn::None, ParseFn::Binary, Precedence::PrecFactor),
+            TokenType::TokenSlashEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenStar => (ParseFn::None, ParseFn::Binary, Precedence::PrecFactor),
+            TokenType::TokenStarEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenStarStar => (ParseFn::None, ParseFn::Binary, Precedence::PrecExponent),
+            TokenType::TokenPercent => (ParseFn::None, ParseFn::Binary, Precedence::PrecFactor),
+            TokenType::TokenPercentEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenTilde => (ParseFn::Unary, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenTildeSlash => (ParseFn::None, ParseFn::Binary, Precedence::PrecFactor),
+            TokenType::TokenAmpersand => (ParseFn::None, ParseFn::Binary, Precedence::PrecBitAnd),
+            TokenType::TokenPipe => (ParseFn::None, ParseFn::Binary, Precedence::PrecBitOr),
+            TokenType::TokenCaret => (ParseFn::None, ParseFn::Binary, Precedence::PrecBitXor),
+            TokenType::TokenBang => (ParseFn::Unary, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenBangEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecEquality),
+            TokenType::TokenEqual => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenEqualEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecEquality),
+            TokenType::TokenGreater => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
+            TokenType::TokenGreaterEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
+            TokenType::TokenGreaterGreater => (ParseFn::None, ParseFn::Binary, Precedence::PrecShift),
+            TokenType::TokenLess => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
+            TokenType::TokenLessEqual => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
+            TokenType::TokenLessLess => (ParseFn::None, ParseFn::Binary, Precedence::PrecShift),
+            TokenType::TokenIdentifier => (ParseFn::Variable, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenString => (ParseFn::String, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenInterpolation => (ParseFn::Interpolation, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenNumber => (ParseFn::Number, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenAnd => (ParseFn::None, ParseFn::And, Precedence::PrecAnd),
+            TokenType::TokenBreak => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenCase => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenClass => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenConst => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenContinue => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenDefault => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenElse => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenFalse => (ParseFn::Literal, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenFor => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenFun => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenIf => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenIn => (ParseFn::None, ParseFn::Binary, Precedence::PrecComparison),
+            TokenType::TokenNil => (ParseFn::Literal, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenOr => (ParseFn::None, ParseFn::Or, Precedence::PrecOr),
+            TokenType::TokenPrint => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenReturn => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenSuper => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenSwitch => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenThis => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenTrue => (ParseFn::Literal, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenVar => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenWhile => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenError => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+            TokenType::TokenEof => (ParseFn::None, ParseFn::None, Precedence::PrecNone),
+        }
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(&self.previous.clone(), message);
+    }
+
+    fn error_at(&mut self, token: &Token, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        let diagnostic = Diagnostic::new(Severity::Error, message, token);
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn advance(&mut self) {
+        self.previous = self.current.clone();
+
+        loop {
+            self.current = self.scanner.scan_token();
+            //println!("token read is {:?}", &self.current);
+
+            if self.current.token_type != TokenType::TokenError {
+                break;
+            }
+
+            self.error_at_current(&self.current.content.clone());
+        }
+    }
+
+    fn error_at_current(&mut self, message: &str) {
+        self.error_at(&self.current.clone(), message);
+    }
+
+    // Returns every diagnostic reported while compiling. Compilation
+    // succeeded when none of them is an error.
+    pub fn compile(&mut self, vm: &mut VM) -> Vec<Diagnostic> {
+        self.advance();
+
+        while !self.match_token(TokenType::TokenEof) {
+            self.declaration(vm);
+        }
+
+        self.end_compiler(vm);
+
+        if vm.options().warn_undeclared_globals {
+            self.warn_undeclared_globals();
+        }
+        return std::mem::take(&mut self.diagnostics);
+    }
+
+    // Assignments to globals that no `var` or `const` in the script
+    // declares fail at runtime, most likely because of a typo.
+    fn warn_undeclared_globals(&mut self) {
+        let assignments = std::mem::take(&mut self.compiler.global_assignments);
+        for name in assignments {
+            if !self.compiler.declared_globals.contains(&name.content) {
+                self.diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    "Assignment to a global that is never declared.",
+                    &name,
+                ));
+            }
+        }
+    }
+
+    fn declaration(&mut self, vm: &mut VM) {
+        if self.match_token(TokenType::TokenVar) {
+            self.var_declaration(vm);
+        } else if self.match_token(TokenType::TokenConst) {
+            self.const_declaration(vm);
+        } else {
+            self.statement(vm);
+        }
+
+        if self.panic_mode {
+            self.synchronize(vm);
+        }
+    }
+
+    fn var_declaration(&mut self, vm: &mut VM) {
+        let global = self.parse_variable("Expect variable name.", vm);
+
+        if self.match_token(TokenType::TokenEqual) {
+            self.expression(vm);
+        } else {
+            self.emit_byte(map_opcode_to_binary(OpCode::OpNil));
+        }
+
+        self.consume(
+            TokenType::TokenSemicolon,
+            "Expect ';' after variable declaration.",
+        );
+
+        self.define_variable(global);
+    }
+
+    fn const_declaration(&mut self, vm: &mut VM) {
+        let global = self.parse_variable("Expect constant name.", vm);
+        let name = self.previous.content.clone();
+
+        self.consume(TokenType::TokenEqual, "Expect '=' after constant name.");
+        let initializer_start = self.current_chunk().count();
+        self.expression(vm);
+        self.consume(
+            TokenType::TokenSemicolon,
+            "Expect ';' after constant declaration.",
+        );
+
+        if self.compiler.scope_depth > 0 {
+            self.compiler.locals[(self.compiler.local_count - 1) as usize].constant = true;
+            self.mark_initialized();
+            return;
+        }
+
+        let value = self.literal_value(initializer_start);
+        self.compiler.constant_globals.insert(name, value);
+        self.emit_bytes(map_opcode_to_binary(OpCode::OpDefineConstGlobal), global);
+    }
+
+    fn parse_variable(&mut self, message: &str, vm: &mut VM) -> u8 {
+        self.consume(TokenType::TokenIdentifier, message);
+        self.declare_variable(vm);
+
+        if self.compiler.scope_depth > 0 {
+            return 0;
+        }
+
+        return self.identifier_constant(&self.previous.clone(), vm);
+    }
+
+    fn define_variable(&mut self, global: u8) {
+        if self.compiler.scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+
+        self.emit_bytes(map_opcode_to_binary(OpCode::OpDefineGlobal), global);
+    }
+
+    fn mark_initialized(&mut self) {
+        self.compiler.locals[(self.compiler.local_count - 1) as usize].depth =
+            self.compiler.scope_depth;
+    }
+
+    fn identifier_constant(&mut self, token: &Token, vm: &mut VM) -> u8 {
+        let obj = vm.get_or_create_string_object(&token.content);
+        return self.make_constant(obj);
+    }
+
+    fn declare_variable(&mut self, vm: &mut VM) {
+        if self.compiler.scope_depth == 0 {
+            if self.compiler.constant_globals.contains_key(&self.previous.content) {
+                self.error("Already a constant with this name.");
+            }
+            let name = self.previous.content.clone();
+            self.compiler.declared_globals.insert(name);
+            return;
+        }
+
+        let name = self.previous.clone();
+        let mut i = self.compiler.local_count - 1;
+
+        while i >= 0 {
+            let local = &self.compiler.locals[i as usize];
+
+            if local.depth != -1isize && local.depth < self.compiler.scope_depth {
+                break;
+            }
+
+            if Parser::identifiers_equal(&name, &local.name) {
+                self.error("Already a variable with this name in this scope.");
+            }
+
+            i = i - 1;
+        }
+
+        self.add_local(name, vm);
+    }
+
+    fn identifiers_equal(a: &Token, b: &Token) -> bool {
+        a.content == b.content
+    }
+
+    fn add_local(&mut self, name: Token, _: &mut VM) {
+        if self.compiler.local_count == UINT8_COUNT as isize {
+            self.error("Too many local variables in function.");
+            return;
+        }
+
+        let local = &mut self.compiler.locals[self.compiler.local_count as usize];
+        self.compiler.local_count = self.compiler.local_count + 1;
+
+        local.name = name;
+        local.depth = -1;
+        local.constant = false;
+    }
+
+    fn synchronize(&mut self, _: &mut VM) {
+        self.panic_mode = false;
+
+        while self.current.token_type != TokenType::TokenEof {
+            if self.previous.token_type == TokenType::TokenSemicolon {
+                return;
+            }
+
+            match self.current.token_type {
+                TokenType::TokenClass => return,
+                TokenType::TokenFun => return,
+                TokenType::TokenVar => return,
+                TokenType::TokenConst => return,
+                TokenType::TokenFor => return,
+                TokenType::TokenIf => return,
+                TokenType::TokenSwitch => return,
+                TokenType::TokenWhile => return,
+                TokenType::TokenPrint => return,
+                TokenType::TokenReturn => return,
+                TokenType::TokenBreak => return,
+                TokenType::TokenContinue => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    fn statement(&mut self, vm: &mut VM) {
+        if self.match_token(TokenType::TokenPrint) {
+            self.print_statement(vm);
+        } else if self.match_token(TokenType::TokenFor) {
+            self.for_statement(vm);
+        } else if self.match_token(TokenType::TokenIf) {
+            self.if_statement(vm);
+        } else if self.match_token(TokenType::TokenSwitch) {
+            self.switch_statement(vm);
+        } else if self.match_token(TokenType::TokenWhile) {
+            self.while_statement(vm);
+        } else if self.match_token(TokenType::TokenBreak) {
+            self.break_statement();
+        } else if self.match_token(TokenType::TokenContinue) {
+            self.continue_statement();
+        } else if self.match_token(TokenType::TokenLeftBrace) {
+            self.begin_scope();
+            self.block(vm);
+            self.end_scope();
+        } else {
+            self.expression_statement(vm);
+        }
+    }
+
+    fn for_statement(&mut self, vm: &mut VM) {
+        self.begin_scope();
+        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'for'.");
+        if self.is_for_in() {
+            self.for_in_statement(vm);
+            self.end_scope();
+            return;
+        }
+
+        if self.match_token(TokenType::TokenSemicolon) {
+            // No initializer.
+        } else if self.match_token(TokenType::TokenVar) {
+            self.var_declaration(vm);
+        } else {
+            self.expression_statement(vm);
+        }
+        
+        let mut loop_start = self.current_chunk().count();
+        let mut exit_jump: Option<usize> = None;
+        if !self.match_token(TokenType::TokenSemicolon) {
+            self.expression(vm);
+            self.consume(TokenType::TokenSemicolon, "Expect ';' after loop condition.");
+
+            // Jump out of the loop if the condition is false.
+            exit_jump = Some(self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse)));
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop)); // Condition.
+        }
+
+        if !self.match_token(TokenType::TokenRightParen) {
+            let body_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
+            let increment_start = self.current_chunk().count();
+            self.expression(vm);
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+            self.consume(TokenType::TokenRightParen, "Expect ')' after for clauses.");
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.begin_loop(loop_start);
+        self.statement(vm);
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        }
+        self.end_loop();
+        self.end_scope();
+    }
+
+    // Looks past the current token for `x in` or `var x in`.
+    fn is_for_in(&mut self) -> bool {
+        let checkpoint = self.scanner.checkpoint();
+        let mut token_type = self.current.token_type;
+        if token_type == TokenType::TokenVar {
+            token_type = self.scanner.scan_token().token_type;
+        }
+
+        let for_in = token_type == TokenType::TokenIdentifier
+            && self.scanner.scan_token().token_type == TokenType::TokenIn;
+        self.scanner.rewind(checkpoint);
+        for_in
+    }
+
+    // `for (x in iterable) body`, after the opening parenthesis. OpIterNext
+    // pushes the next element for the body to bind, or jumps out of the
+    // loop once there are none left.
+    fn for_in_statement(&mut self, vm: &mut VM) {
+        self.match_token(TokenType::TokenVar);
+        self.consume(TokenType::TokenIdentifier, "Expect variable name.");
+        let name = self.previous.clone();
+        self.consume(TokenType::TokenIn, "Expect 'in' after loop variable.");
+        let in_span = self.previous.span;
+
+        self.expression(vm);
+        self.consume(TokenType::TokenRightParen, "Expect ')' after for-in clauses.");
+
+        // The iterable and the position of its next element stay on the
+        // stack as hidden locals.
+        self.add_local(Token::new_dummy_token(), vm);
+        self.mark_initialized();
+        let iterable = (self.compiler.local_count - 1) as u8;
+        self.emit_constant(Value::Number(0.0));
+        self.add_local(Token::new_dummy_token(), vm);
+        self.mark_initialized();
+
+        let loop_start = self.current_chunk().count();
+        self.emit_byte_at(map_opcode_to_binary(OpCode::OpIterNext), in_span);
+        self.emit_byte_at(iterable, in_span);
+        self.emit_byte_at(0xFF, in_span);
+        self.emit_byte_at(0xFF, in_span);
+        let exit_jump = self.current_chunk().count() - 2;
+
+        self.begin_loop(loop_start);
+        self.begin_scope();
+        self.add_local(name, vm);
+        self.mark_initialized();
+        self.statement(vm);
+        self.end_scope();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.end_loop();
+    }
+
+    fn while_statement(&mut self, vm: &mut VM) {
+        let loop_start = self.current_chunk().count();
+        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'while'.");
+        self.expression(vm);
+        self.consume(TokenType::TokenRightParen, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.begin_loop(loop_start);
+        self.statement(vm);
+        self.emit_loop(loop_start);
+        self.patch_jump(exit_jump);
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.end_loop();
+    }
+
+    fn begin_loop(&mut self, start: usize) {
+        self.compiler.loops.push(Loop {
+            start: start,
+            scope_depth: self.compiler.scope_depth,
+            break_jumps: vec![],
+        });
+    }
+
+    // Must be called once the loop's exit code is emitted: `break` jumps
+    // land right after it.
+    fn end_loop(&mut self) {
+        let finished_loop = self.compiler.loops.pop().unwrap();
+        for break_jump in finished_loop.break_jumps {
+            self.patch_jump(break_jump);
+        }
+    }
+
+    fn break_statement(&mut self) {
+        let scope_depth = match self.compiler.loops.last() {
+            Some(innermost) => innermost.scope_depth,
+            None => {
+                self.error("Can't use 'break' outside of a loop.");
+                self.consume(TokenType::TokenSemicolon, "Expect ';' after 'break'.");
+                return;
+            }
+        };
+        self.consume(TokenType::TokenSemicolon, "Expect ';' after 'break'.");
+
+        self.discard_locals(scope_depth);
+        let jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
+        self.compiler.loops.last_mut().unwrap().break_jumps.push(jump);
+    }
+
+    fn continue_statement(&mut self) {
+        let (start, scope_depth) = match self.compiler.loops.last() {
+            Some(innermost) => (innermost.start, innermost.scope_depth),
+            None => {
+                self.error("Can't use 'continue' outside of a loop.");
+                self.consume(TokenType::TokenSemicolon, "Expect ';' after 'continue'.");
+                return;
+            }
+        };
+        self.consume(TokenType::TokenSemicolon, "Expect ';' after 'continue'.");
+
+        self.discard_locals(scope_depth);
+        self.emit_loop(start);
+    }
+
+    // Pops the locals declared deeper than `depth` without forgetting them:
+    // the code following the jump still sees them in scope.
+    fn discard_locals(&mut self, depth: isize) {
+        let mut i = self.compiler.local_count - 1;
+        while i >= 0 && self.compiler.locals[i as usize].depth > depth {
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+            i = i - 1;
+        }
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_byte(map_opcode_to_binary(OpCode::OpLoop));
+        let offset = self.current_chunk().count() - loop_start + 2;
+        if offset > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+
+        self.emit_byte(((offset >> 8) & 0xFF) as u8);
+        self.emit_byte((offset & 0xFF) as u8);
+    }
+
+    fn if_statement(&mut self, vm: &mut VM) {
+        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'if'.");
+        self.expression(vm);
+        self.consume(TokenType::TokenRightParen, "Expect ')' after condition.");
+
+        let then_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.statement(vm);
+        let else_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
+        self.patch_jump(then_jump);
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+
+        if self.match_token(TokenType::TokenElse) {
+            self.statement(vm);
+        }
+        self.patch_jump(else_jump);
+    }
+
+    // Compiles to a chain of tests: each case compares the subject against
+    // its values in turn and jumps to its body on the first match. Bodies do
+    // not fall through, and `break` still refers to the enclosing loop.
+    fn switch_statement(&mut self, vm: &mut VM) {
+        let switch_span = self.previous.span;
+        self.consume(TokenType::TokenLeftParen, "Expect '(' after 'switch'.");
+        self.begin_scope();
+        self.expression(vm);
+        self.consume(TokenType::TokenRightParen, "Expect ')' after switch value.");
+
+        // The subject stays on the stack as a hidden local for the cases to
+        // compare against.
+        self.add_local(Token::new_dummy_token(), vm);
+        self.mark_initialized();
+        let subject = (self.compiler.local_count - 1) as u8;
+
+        self.consume(TokenType::TokenLeftBrace, "Expect '{' before switch cases.");
+
+        let mut table = self.switch_table(switch_span, vm);
+        let mut end_jumps = vec![];
+        let mut constants: Vec<Value> = vec![];
+        let mut has_default = false;
+
+        while !self.check(TokenType::TokenRightBrace) && !self.check(TokenType::TokenEof) {
+            if has_default {
+                self.error_at_current("'default' must be the last case in a switch.");
+            }
+
+            let mut miss_jump = None;
+            if self.match_token(TokenType::TokenCase) {
+                let mut body_jumps = vec![];
+                let mut case_values = vec![];
+                loop {
+                    let value_token = self.current.clone();
+                    let value = if table.is_some() {
+                        // Every case value is a single literal, or there
+                        // would be no table.
+                        self.advance();
+                        self.case_literal(&value_token, vm)
+                    } else {
+                        self.emit_bytes(map_opcode_to_binary(OpCode::OpGetLocal), subject);
+                        let value_start = self.current_chunk().count();
+                        self.expression(vm);
+
+                        self.emit_byte(map_opcode_to_binary(OpCode::OpEqual));
+                        let next_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
+                        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+                        body_jumps.push(self.emit_jump(map_opcode_to_binary(OpCode::OpJump)));
+                        self.patch_jump(next_jump);
+                        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+                        self.literal_value(value_start)
+                    };
+
+                    if let Some(value) = value {
+                        if constants.iter().any(|c| values_equal(c.clone(), value.clone())) {
+                            self.error_at(&value_token, "Duplicate case value.");
+                        } else {
+                            constants.push(value.clone());
+                            case_values.push(value);
+                        }
+                    }
+
+                    if !self.match_token(TokenType::TokenComma) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::TokenColon, "Expect ':' after case value.");
+
+                let body_start = self.current_chunk().count();
+                match &mut table {
+                    Some(table) => {
+                        for value in case_values {
+                            if let Some(i) = table.values.iter().position(|v| *v == value) {
+                                table.targets[i] = Some(body_start);
+                            }
+                        }
+                    }
+                    None => {
+                        miss_jump = Some(self.emit_jump(map_opcode_to_binary(OpCode::OpJump)));
+                        for body_jump in body_jumps {
+                            self.patch_jump(body_jump);
+                        }
+                    }
+                }
+            } else if self.match_token(TokenType::TokenDefault) {
+                self.consume(TokenType::TokenColon, "Expect ':' after 'default'.");
+                has_default = true;
+                if let Some(table) = &mut table {
+                    table.default_target = Some(self.current_chunk().count());
+                }
+            } else {
+                // Compile the stray statements as a body anyway so parsing
+                // resumes at the next case.
+                self.error_at_current("Expect 'case' or 'default' in switch body.");
+            }
+
+            self.begin_scope();
+            while !self.check(TokenType::TokenCase)
+                && !self.check(TokenType::TokenDefault)
+                && !self.check(TokenType::TokenRightBrace)
+                && !self.check(TokenType::TokenEof)
+            {
+                self.declaration(vm);
+            }
+            self.end_scope();
+
+            if let Some(miss_jump) = miss_jump {
+                end_jumps.push(self.emit_jump(map_opcode_to_binary(OpCode::OpJump)));
+                self.patch_jump(miss_jump);
+            } else if table.is_some() && !has_default {
+                end_jumps.push(self.emit_jump(map_opcode_to_binary(OpCode::OpJump)));
+            }
+        }
+
+        self.consume(TokenType::TokenRightBrace, "Expect '}' after switch cases.");
+        if let Some(table) = table {
+            self.patch_switch_table(table);
+        }
+        for end_jump in end_jumps {
+            self.patch_jump(end_jump);
+        }
+        self.end_scope();
+    }
+
+    // Looks ahead through the switch body and, if every case value is an
+    // integer literal and the values are close together, or every case
+    // value is a string literal, emits a jump table that takes the subject
+    // straight to its case. Otherwise the cases are tested one by one.
+    fn switch_table(&mut self, span: Span, vm: &mut VM) -> Option<SwitchTable> {
+        let checkpoint = self.scanner.checkpoint();
+        let values = self.scan_case_values(vm);
+        self.scanner.rewind(checkpoint);
+
+        let values = values?;
+        if values.len() < 2 {
+            return None;
+        }
+
+        let integer = |value: &Value| match value {
+            Value::Number(n) => n.fract() == 0.0 && *n < 65536.0,
+            _ => false,
+        };
+        let table_start = self.current_chunk().count();
+        let (values, entries) = if values.iter().all(integer) {
+            let numbers: Vec<f64> = values.iter().map(|v| v.as_number()).collect();
+            let min = numbers.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = numbers.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let count = (max - min) as usize + 1;
+            if count > u8::MAX as usize || count > 2 * numbers.len() {
+                return None;
+            }
+
+            let min_constant = self.make_constant(Value::Number(min));
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpSwitchNumber), span);
+            self.emit_byte_at(min_constant, span);
+            self.emit_byte_at(count as u8, span);
+            for _ in 0..count {
+                self.emit_byte_at(0xFF, span);
+                self.emit_byte_at(0xFF, span);
+            }
+            let values = (0..count).map(|i| Value::Number(min + i as f64)).collect();
+            let entries = (0..count).map(|i| table_start + 3 + 2 * i).collect();
+            (values, entries)
+        } else if values.iter().all(|v| v.is_string()) && values.len() <= u8::MAX as usize {
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpSwitchString), span);
+            self.emit_byte_at(values.len() as u8, span);
+            let mut entries = vec![];
+            for value in &values {
+                let constant = self.make_constant(value.clone());
+                self.emit_byte_at(constant, span);
+                entries.push(self.current_chunk().count());
+                self.emit_byte_at(0xFF, span);
+                self.emit_byte_at(0xFF, span);
+            }
+            (values, entries)
+        } else {
+            return None;
+        };
+
+        let default_entry = self.current_chunk().count();
+        self.emit_byte_at(0xFF, span);
+        self.emit_byte_at(0xFF, span);
+
+        let targets = vec![None; values.len()];
+        return Some(SwitchTable {
+            values: values,
+            entries: entries,
+            targets: targets,
+            default_entry: default_entry,
+            default_target: None,
+            end: self.current_chunk().count(),
+        });
+    }
+
+    // The distinct case values of the switch body starting at the current
+    // token, or None if any of them is not a single number or string
+    // literal.
+    fn scan_case_values(&mut self, vm: &mut VM) -> Option<Vec<Value>> {
+        let mut values: Vec<Value> = vec![];
+        let mut depth = 0;
+        let mut token = self.current.clone();
+        loop {
+            match token.token_type {
+                TokenType::TokenEof => return None,
+                TokenType::TokenLeftBrace => depth = depth + 1,
+                TokenType::TokenRightBrace if depth == 0 => return Some(values),
+                TokenType::TokenRightBrace => depth = depth - 1,
+                TokenType::TokenCase if depth == 0 => loop {
+                    let value_token = self.scanner.scan_token();
+                    let value = self.case_literal(&value_token, vm)?;
+                    if !values.contains(&value) {
+                        values.push(value);
+                    }
+
+                    match self.scanner.scan_token().token_type {
+                        TokenType::TokenComma => {}
+                        TokenType::TokenColon => break,
+                        _ => return None,
+                    }
+                },
+                _ => {}
+            }
+            token = self.scanner.scan_token();
+        }
+    }
+
+    fn case_literal(&mut self, token: &Token, vm: &mut VM) -> Option<Value> {
+        match token.token_type {
+            TokenType::TokenNumber => return Some(Value::Number(token.content.parse().unwrap())),
+            TokenType::TokenString => {
+                let literal = token.literal.clone().unwrap_or_default();
+                return Some(vm.get_or_create_string_object(&literal));
+            }
+            _ => return None,
+        }
+    }
+
+    fn patch_switch_table(&mut self, table: SwitchTable) {
+        let end_target = self.current_chunk().count();
+        let default_target = table.default_target.unwrap_or(end_target);
+        for (entry, target) in table.entries.iter().zip(table.targets.iter()) {
+            self.patch_table_entry(*entry, target.unwrap_or(default_target), table.end);
+        }
+        self.patch_table_entry(table.default_entry, default_target, table.end);
+    }
+
+    fn patch_table_entry(&mut self, entry: usize, target: usize, table_end: usize) {
+        let jump = target - table_end;
+        if jump > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+        }
+
+        self.current_chunk().code[entry] = ((jump >> 8) & 0xFF) as u8;
+        self.current_chunk().code[entry + 1] = (jump & 0xFF) as u8;
+    }
+
+    // The value of the expression compiled from `start` on, if it was a
+    // single literal.
+    fn literal_value(&mut self, start: usize) -> Option<Value> {
+        let chunk = self.current_chunk();
+        match chunk.code[start..] {
+            [op, index] if op == map_opcode_to_binary(OpCode::OpConstant) => {
+                return Some(chunk.constants[index as usize].clone());
+            }
+            [op] if op == map_opcode_to_binary(OpCode::OpTrue) => return Some(Value::Boolean(true)),
+            [op] if op == map_opcode_to_binary(OpCode::OpFalse) => return Some(Value::Boolean(false)),
+            [op] if op == map_opcode_to_binary(OpCode::OpNil) => return Some(Value::Nil),
+            _ => return None,
+        }
+    }
+
+    fn emit_jump(&mut self, instruction: u8) -> usize {
+        self.emit_byte(instruction);
+        self.emit_byte(0xFF);
+        self.emit_byte(0xFF);
+        self.current_chunk().count() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        let jump = self.current_chunk().count() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+        }
+
+        self.current_chunk().code[offset] = ((jump >> 8) & 0xFF) as u8;
+        self.current_chunk().code[offset + 1] = (jump & 0xFF) as u8;
+    }
+
+    fn block(&mut self, vm: &mut VM) {
+        while !self.check(TokenType::TokenRightBrace) && !self.check(TokenType::TokenEof) {
+            self.declaration(vm);
+        }
+
+        self.consume(TokenType::TokenRightBrace, "Expect '}' after block.");
+    }
+
+    fn begin_scope(&mut self) {
+        self.compiler.scope_depth = self.compiler.scope_depth + 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.compiler.scope_depth = self.compiler.scope_depth - 1;
+
+        while self.compiler.local_count > 0
+            && self.compiler.locals[(self.compiler.local_count - 1) as usize].depth
+                > self.compiler.scope_depth
+        {
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+            self.compiler.local_count = self.compiler.local_count - 1;
+        }
+    }
+
+    fn expression_statement(&mut self, vm: &mut VM) {
+        self.expression(vm);
+        self.consume(TokenType::TokenSemicolon, "Expect ';' after expression.");
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop))
+    }
+
+    fn match_token(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn check(&mut self, token_type: TokenType) -> bool {
+        self.current.token_type == token_type
+    }
+
+    fn print_statement(&mut self, vm: &mut VM) {
+        self.expression(vm);
+        self.consume(TokenType::TokenSemicolon, "Expect ';' after value.");
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPrint));
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) {
+        if self.current.token_type == token_type {
+            self.advance();
+            return;
+        }
+
+        //println!("consume failed. Expected {:?}, got {:?}", token_type, self.current);
+
+        self.error_at_current(message);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let span = self.previous.span;
+        self.current_chunk().write_chunk(byte, span);
+    }
+
+    // Attributes the instruction to `span` rather than to the last token
+    // consumed, so runtime errors point at the operator that failed.
+    fn emit_byte_at(&mut self, byte: u8, span: Span) {
+        self.current_chunk().write_chunk(byte, span);
+    }
+
+    fn current_chunk(&mut self) -> &mut Chunk {
+        &mut self.compiling_chunk
+    }
+
+    fn emit_bytes(&mut self, byte1: u8, byte2: u8) {
+        self.emit_byte(byte1);
+        self.emit_byte(byte2);
+    }
+
+    fn end_compiler(&mut self, vm: &mut VM) {
+        self.emit_return(vm);
+    }
+
+    fn emit_return(&mut self, _: &mut VM) {
+        self.emit_byte(map_opcode_to_binary(OpCode::OpReturn));
+    }
+
+    fn expression(&mut self, vm: &mut VM) {
+        self.parse_precedence(Precedence::PrecAssignment, vm);
+    }
+
+    fn number(&mut self, _: &mut VM) {
+        let value: f64 = self.previous.content.parse().unwrap();
+        self.emit_constant(Value::Number(value));
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit_bytes(map_opcode_to_binary(OpCode::OpConstant), constant);
+    }
+
+    pub fn make_constant(&mut self, value: Value) -> u8 {
+        let constant = self.current_chunk().add_constant(value);
+        if constant > (u8::MAX) as usize {
+            self.error("Too many constants in one chunk.");
+            return 0u8;
+        }
+
+        return constant as u8;
+    }
+
+    fn grouping(&mut self, vm: &mut VM) {
+        self.expression(vm);
+        self.consume(TokenType::TokenRightParen, "Expect ')' after expression.");
+    }
+
+    fn unary(&mut self, vm: &mut VM) {
+        let operator_type: TokenType = self.previous.token_type;
+        let operator_span = self.previous.span;
+
+        self.parse_precedence(Precedence::PrecUnary, vm);
+
+        match operator_type {
+            TokenType::TokenBang => {
+                self.emit_byte_at(map_opcode_to_binary(OpCode::OpNot), operator_span)
+            }
+            TokenType::TokenMinus => {
+                self.emit_byte_at(map_opcode_to_binary(OpCode::OpNegate), operator_span)
+            }
+            TokenType::TokenTilde => {
+                self.emit_byte_at(map_opcode_to_binary(OpCode::OpBitNot), operator_span)
+            }
+            _ => {}
+        }
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence, vm: &mut VM) {
+        self.advance();
+        let rule = Parser::get_rule(self.previous.token_type);
+
+        if rule.prefix == ParseFn::None {
+            self.error("Expect expression.");
+            return;
+        }
+
+        let can_assign = precedence <= Precedence::PrecAssignment;
+        self.run_rule(rule.prefix, can_assign, vm);
+
+        while precedence <= Parser::get_rule(self.current.token_type).precedence {
+            self.advance();
+            let new_rule = Parser::get_rule(self.previous.token_type);
+            let infix = new_rule.infix;
+            self.run_rule(infix, can_assign, vm);
+        }
+
+        if can_assign
+            && (self.match_token(TokenType::TokenEqual)
+                || self.match_compound_assignment().is_some())
+        {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    fn string(&mut self, vm: &mut VM) {
+        let literal = self.previous.literal.clone().unwrap_or_default();
+
+        let value = vm.get_or_create_string_object(&literal);
+
+        self.emit_constant(value);
+    }
+
+    fn call(&mut self, vm: &mut VM) {
+        let paren_span = self.previous.span;
+        let arg_count = self.argument_list(vm);
+        self.emit_byte_at(map_opcode_to_binary(OpCode::OpCall), paren_span);
+        self.emit_byte_at(arg_count, paren_span);
+    }
+
+    fn argument_list(&mut self, vm: &mut VM) -> u8 {
+        let mut arg_count: usize = 0;
+        if !self.check(TokenType::TokenRightParen) {
+            loop {
+                self.expression(vm);
+                if arg_count == u8::MAX as usize {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                arg_count = arg_count + 1;
+
+                if !self.match_token(TokenType::TokenComma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::TokenRightParen, "Expect ')' after arguments.");
+        return arg_count as u8;
+    }
+
+    fn list(&mut self, vm: &mut VM) {
+        let mut element_count: usize = 0;
+        while !self.check(TokenType::TokenRightBracket) && !self.check(TokenType::TokenEof) {
+            self.expression(vm);
+            if element_count == u8::MAX as usize {
+                self.error("Can't have more than 255 elements in a list literal.");
+            }
+            element_count = element_count + 1;
+
+            // A trailing comma is allowed.
+            if !self.match_token(TokenType::TokenComma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::TokenRightBracket, "Expect ']' after list elements.");
+        self.emit_bytes(map_opcode_to_binary(OpCode::OpBuildList), element_count as u8);
+    }
+
+    // In expression position `{` starts a map literal; statements starting
+    // with `{` are still blocks.
+    fn map(&mut self, vm: &mut VM) {
+        let brace_span = self.previous.span;
+        let mut entry_count: usize = 0;
+        while !self.check(TokenType::TokenRightBrace) && !self.check(TokenType::TokenEof) {
+            self.expression(vm);
+            self.consume(TokenType::TokenColon, "Expect ':' after map key.");
+            self.expression(vm);
+            if entry_count == u8::MAX as usize {
+                self.error("Can't have more than 255 entries in a map literal.");
+            }
+            entry_count = entry_count + 1;
+
+            // A trailing comma is allowed.
+            if !self.match_token(TokenType::TokenComma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::TokenRightBrace, "Expect '}' after map entries.");
+        self.emit_byte_at(map_opcode_to_binary(OpCode::OpBuildMap), brace_span);
+        self.emit_byte_at(entry_count as u8, brace_span);
+    }
+
+    fn index(&mut self, can_assign: bool, vm: &mut VM) {
+        let bracket_span = self.previous.span;
+        self.expression(vm);
+        self.consume(TokenType::TokenRightBracket, "Expect ']' after index.");
+        let compound_opcode = if can_assign {
+            self.match_compound_assignment()
+        } else {
+            None
+        };
+
+        if can_assign && self.match_token(TokenType::TokenEqual) {
+            self.expression(vm);
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexSet), bracket_span);
+        } else if let Some(opcode) = compound_opcode {
+            // The target and index are copied for the store, so `xs[f()] += 1`
+            // calls `f` once.
+            let operator_span = self.previous.span;
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpDupTwo), bracket_span);
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexGet), bracket_span);
+            self.expression(vm);
+            self.emit_byte_at(map_opcode_to_binary(opcode), operator_span);
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexSet), bracket_span);
+        } else if self.match_token(TokenType::TokenPlusPlus)
+            || self.match_token(TokenType::TokenMinusMinus)
+        {
+            // Postfix: the old value is rotated under the target and index,
+            // and stays on the stack once the new one is stored.
+            let opcode = Parser::increment_opcode(self.previous.token_type);
+            let operator_span = self.previous.span;
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpDupTwo), bracket_span);
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexGet), bracket_span);
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpRotate), bracket_span);
+            self.increment_element(opcode, operator_span, bracket_span);
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        } else {
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexGet), bracket_span);
+        }
+    }
+
+    // With a target and an index on the stack, stores the element there
+    // plus or minus one and leaves the new value.
+    fn increment_element(&mut self, opcode: OpCode, operator_span: Span, bracket_span: Span) {
+        self.emit_byte_at(map_opcode_to_binary(OpCode::OpDupTwo), bracket_span);
+        self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexGet), bracket_span);
+        self.emit_constant(Value::Number(1.0));
+        self.emit_byte_at(map_opcode_to_binary(opcode), operator_span);
+        self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexSet), bracket_span);
+    }
+
+    // `receiver.name` reads a property; `receiver.name(args)` invokes a
+    // method in a single instruction.
+    fn dot(&mut self, vm: &mut VM) {
+        self.consume(TokenType::TokenIdentifier, "Expect property name after '.'.");
+        let name_token = self.previous.clone();
+        let name = self.identifier_constant(&name_token, vm);
+
+        if self.match_token(TokenType::TokenLeftParen) {
+            let arg_count = self.argument_list(vm);
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpInvoke), name_token.span);
+            self.emit_byte_at(name, name_token.span);
+            self.emit_byte_at(arg_count, name_token.span);
+        } else {
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpGetProperty), name_token.span);
+            self.emit_byte_at(name, name_token.span);
+        }
+    }
+
+    // `"a ${b} c"` is scanned as an interpolation token holding "a ", the
+    // tokens of `b`, then a string token holding " c". Every segment and
+    // expression is pushed, then joined by a single OpBuildString.
+    fn interpolation(&mut self, vm: &mut VM) {
+        let mut part_count = 0;
+
+        loop {
+            let segment = self.previous.literal.clone().unwrap_or_default();
+            if !segment.is_empty() {
+                let value = vm.get_or_create_string_object(&segment);
+                self.emit_constant(value);
+                part_count = part_count + 1;
+            }
+
+            // A segment resuming the string right away means `${}` was empty.
+            let resumes_string = self.check(TokenType::TokenString)
+                || self.check(TokenType::TokenInterpolation);
+            if resumes_string && self.current.content.starts_with('}') {
+                self.error_at_current("Expect expression.");
+            } else {
+                self.expression(vm);
+                part_count = part_count + 1;
+            }
+
+            if !self.match_token(TokenType::TokenInterpolation) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::TokenString, "Expect '}' after interpolated expression.");
+        let segment = self.previous.literal.clone().unwrap_or_default();
+        if !segment.is_empty() {
+            let value = vm.get_or_create_string_object(&segment);
+            self.emit_constant(value);
+            part_count = part_count + 1;
+        }
+
+        if part_count > u8::MAX as usize {
+            self.error("Too many parts in string interpolation.");
+        }
+        self.emit_bytes(map_opcode_to_binary(OpCode::OpBuildString), part_count as u8);
+    }
+
+    fn variable(&mut self, can_assign: bool, vm: &mut VM) {
+        self.named_variable(self.previous.clone(), can_assign, vm);
+    }
+
+    fn named_variable(&mut self, name: Token, can_assign: bool, vm: &mut VM) {
+        let (get_op, set_op, arg) = self.resolve_variable(&name, vm);
+        let compound_opcode = if can_assign {
+            self.match_compound_assignment()
+        } else {
+            None
+        };
+
+        if can_assign && self.match_token(TokenType::TokenEqual) {
+            self.check_assignable(&name, &get_op, arg);
+            self.expression(vm);
+            self.emit_byte_at(map_opcode_to_binary(set_op), name.span);
+            self.emit_byte_at(arg, name.span);
+        } else if let Some(opcode) = compound_opcode {
+            // `a += b` is compiled as `a = a + b`.
+            self.check_assignable(&name, &get_op, arg);
+            let operator_span = self.previous.span;
+            self.emit_bytes(map_opcode_to_binary(get_op), arg);
+            self.expression(vm);
+            self.emit_byte_at(map_opcode_to_binary(opcode), operator_span);
+            self.emit_byte_at(map_opcode_to_binary(set_op), name.span);
+            self.emit_byte_at(arg, name.span);
+        } else if self.match_token(TokenType::TokenPlusPlus)
+            || self.match_token(TokenType::TokenMinusMinus)
+        {
+            // Postfix: the old value is left on the stack under the stored one.
+            self.check_assignable(&name, &get_op, arg);
+            let opcode = Parser::increment_opcode(self.previous.token_type);
+            let operator_span = self.previous.span;
+            self.emit_bytes(map_opcode_to_binary(get_op.clone()), arg);
+            self.emit_bytes(map_opcode_to_binary(get_op), arg);
+            self.emit_constant(Value::Number(1.0));
+            self.emit_byte_at(map_opcode_to_binary(opcode), operator_span);
+            self.emit_byte_at(map_opcode_to_binary(set_op), name.span);
+            self.emit_byte_at(arg, name.span);
+            self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        } else if let Some(value) = self.inlined_constant(&name, &get_op) {
+            self.emit_constant(value);
+        } else {
+            self.emit_bytes(map_opcode_to_binary(get_op), arg);
+        }
+    }
+
+    fn prefix_increment(&mut self, vm: &mut VM) {
+        let opcode = Parser::increment_opcode(self.previous.token_type);
+        let operator_span = self.previous.span;
+        self.consume(
+            TokenType::TokenIdentifier,
+            "Expect variable name after increment operator.",
+        );
+
+        let name = self.previous.clone();
+        if self.check(TokenType::TokenLeftBracket) {
+            self.prefix_increment_element(name, opcode, operator_span, vm);
+            return;
+        }
+
+        let (get_op, set_op, arg) = self.resolve_variable(&name, vm);
+        self.check_assignable(&name, &get_op, arg);
+        self.emit_bytes(map_opcode_to_binary(get_op), arg);
+        self.emit_constant(Value::Number(1.0));
+        self.emit_byte_at(map_opcode_to_binary(opcode), operator_span);
+        self.emit_byte_at(map_opcode_to_binary(set_op), name.span);
+        self.emit_byte_at(arg, name.span);
+    }
+
+    // `++xs[i][j]`: every index but the last is read as usual, and the
+    // element at the last one is incremented.
+    fn prefix_increment_element(
+        &mut self,
+        name: Token,
+        opcode: OpCode,
+        operator_span: Span,
+        vm: &mut VM,
+    ) {
+        self.named_variable(name, false, vm);
+        loop {
+            self.advance();
+            let bracket_span = self.previous.span;
+            self.expression(vm);
+            self.consume(TokenType::TokenRightBracket, "Expect ']' after index.");
+
+            if !self.check(TokenType::TokenLeftBracket) {
+                self.increment_element(opcode, operator_span, bracket_span);
+                return;
+            }
+            self.emit_byte_at(map_opcode_to_binary(OpCode::OpIndexGet), bracket_span);
+        }
+    }
+
+    // Reports assignments to locals and globals known to be constant here;
+    // anything else is left to the runtime check in OpSetGlobal. Global
+    // targets are also recorded for the undeclared global warning.
+    fn check_assignable(&mut self, name: &Token, get_op: &OpCode, arg: u8) {
+        let constant = match get_op {
+            OpCode::OpGetLocal => self.compiler.locals[arg as usize].constant,
+            _ => {
+                self.compiler.global_assignments.push(name.clone());
+                self.compiler.constant_globals.contains_key(&name.content)
+            }
+        };
+
+        if constant {
+            self.error_at(name, "Can't assign to a constant.");
+        }
+    }
+
+    // The value of a constant global whose initializer was a literal.
+    fn inlined_constant(&self, name: &Token, get_op: &OpCode) -> Option<Value> {
+        match get_op {
+            OpCode::OpGetGlobal => self.compiler.constant_globals.get(&name.content)?.clone(),
+            _ => None,
+        }
+    }
+
+    fn increment_opcode(operator_type: TokenType) -> OpCode {
+        match operator_type {
+            TokenType::TokenPlusPlus => OpCode::OpAdd,
+            _ => OpCode::OpSubtract,
+        }
+    }
+
+    fn match_compound_assignment(&mut self) -> Option<OpCode> {
+        let opcode = match self.current.token_type {
+            TokenType::TokenPlusEqual => OpCode::OpAdd,
+            TokenType::TokenMinusEqual => OpCode::OpSubtract,
+            TokenType::TokenStarEqual => OpCode::OpMultiply,
+            TokenType::TokenSlashEqual => OpCode::OpDivide,
+            TokenType::TokenPercentEqual => OpCode::OpModulo,
+            _ => return None,
+        };
+        self.advance();
+        Some(opcode)
+    }
+
+    // Returns the get and set instructions for `name` and their operand.
+    fn resolve_variable(&mut self, name: &Token, vm: &mut VM) -> (OpCode, OpCode, u8) {
+        let arg = self.resolve_local(name, vm);
+
+        if arg != -1 {
+            (OpCode::OpGetLocal, OpCode::OpSetLocal, arg as u8)
+        } else {
+            let arg = self.identifier_constant(name, vm);
+            (OpCode::OpGetGlobal, OpCode::OpSetGlobal, arg)
+        }
+    }
+
+    fn resolve_local(&mut self, name: &Token, _: &mut VM) -> isize {
+        let mut i = self.compiler.local_count - 1;
+
+        while i >= 0 {
+            let local = &self.compiler.locals[i as usize];
+            if Parser::identifiers_equal(name, &local.name) {
+                if local.depth == -1 {
+                    self.error("Can't read local variable in its own initializer.");
+                }
+                return i;
+            }
+            i = i - 1;
+        }
+
+        -1
+    }
+
+    fn literal(&mut self, _: &mut VM) {
+        match self.previous.token_type {
+            TokenType::TokenFalse => {
+                self.emit_byte(map_opcode_to_binary(OpCode::OpFalse));
+            }
+            TokenType::TokenNil => {
+                self.emit_byte(map_opcode_to_binary(OpCode::OpNil));
+            }
+            TokenType::TokenTrue => {
+                self.emit_byte(map_opcode_to_binary(OpCode::OpTrue));
+            }
+            _ => {}
+        }
+    }
+
+    fn and(&mut self, vm: &mut VM) {
+        let end_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.parse_precedence(Precedence::PrecAnd, vm);
+        self.patch_jump(end_jump);
+    }
+
+    fn or(&mut self, vm: &mut VM) {
+        let else_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
+        let end_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
+
+        self.patch_jump(else_jump);
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.parse_precedence(Precedence::PrecOr, vm);
+        self.patch_jump(end_jump);
+    }
+
+    // `cond ? a : b` branches like an if statement. The then branch is
+    // delimited by the ':' and may be any expression; the else branch is
+    // parsed at conditional precedence, making the operator right-associative.
+    fn conditional(&mut self, vm: &mut VM) {
+        let else_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfFalse));
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.expression(vm);
+        self.consume(
+            TokenType::TokenColon,
+            "Expect ':' after then branch of conditional expression.",
+        );
+
+        let end_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJump));
+        self.patch_jump(else_jump);
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.parse_precedence(Precedence::PrecConditional, vm);
+        self.patch_jump(end_jump);
+    }
+
+    // `a ?? b` keeps `a` unless it is nil, in which case `b` is evaluated.
+    fn coalesce(&mut self, vm: &mut VM) {
+        let end_jump = self.emit_jump(map_opcode_to_binary(OpCode::OpJumpIfNotNil));
+        self.emit_byte(map_opcode_to_binary(OpCode::OpPop));
+        self.parse_precedence(Precedence::PrecOr, vm);
+        self.patch_jump(end_jump);
+    }
+
+    fn run_rule(&mut self, rule: ParseFn, can_assign: bool, vm: &mut VM) {
+        match rule {
+            ParseFn::Binary => {
+                self.binary(vm);
+            }
+            ParseFn::Grouping => {
+                self.grouping(vm);
+            }
+            ParseFn::Number => {
+                self.number(vm);
+            }
+            ParseFn::Unary => {
+                self.unary(vm);
+            }
+            ParseFn::Literal => {
+                self.literal(vm);
+            }
+            ParseFn::String => {
+                self.string(vm);
+            }
+            ParseFn::Variable => {
+                self.variable(can_assign, vm);
+            }
+            ParseFn::None => {
+                panic!();
+            }
+            ParseFn::And => {
+                self.and(vm);
+            }
+            ParseFn::Or => {
+                self.or(vm);
+            }
+            ParseFn::Interpolation => {
+                self.interpolation(vm);
+            }
+            ParseFn::Increment => {
+                self.prefix_increment(vm);
+            }
+            ParseFn::Conditional => {
+                self.conditional(vm);
+            }
+            ParseFn::Coalesce => {
+                self.coalesce(vm);
+            }
+            ParseFn::Call => {
+                self.call(vm);
+            }
+            ParseFn::List => {
+                self.list(vm);
+            }
+            ParseFn::Map => {
+                self.map(vm);
+            }
+            ParseFn::Index => {
+                self.index(can_assign, vm);
+            }
+            ParseFn::Dot => {
+                self.dot(vm);
+            }
+        }
+    }
+
+    fn binary(&mut self, vm: &mut VM) {
+        let operator_type: TokenType = self.previous.token_type;
+        let operator_span = self.previous.span;
+
+        let rule = Parser::get_rule(operator_type);
+
+        if operator_type == TokenType::TokenStarStar {
+            // Right-associative, and the exponent may itself be negated:
+            // `2 ** -1`, `2 ** 3 ** 2 == 2 ** (3 ** 2)`.
+            self.parse_precedence(Precedence::PrecUnary, vm);
+        } else {
+            self.parse_precedence(get_next_rule(rule.precedence), vm);
+        }
+
+        let opcode = match operator_type {
+            TokenType::TokenBangEqual => OpCode::OpNotEqual,
+            TokenType::TokenEqualEqual => OpCode::OpEqual,
+            TokenType::TokenGreater => OpCode::OpGreater,
+            TokenType::TokenGreaterEqual => OpCode::OpGreaterEqual,
+            TokenType::TokenLess => OpCode::OpLess,
+            TokenType::TokenLessEqual => OpCode::OpLessEqual,
+            TokenType::TokenPlus => OpCode::OpAdd,
+            TokenType::TokenMinus => OpCode::OpSubtract,
+            TokenType::TokenStar => OpCode::OpMultiply,
+            TokenType::TokenSlash => OpCode::OpDivide,
+            TokenType::TokenPercent => OpCode::OpModulo,
+            TokenType::TokenStarStar => OpCode::OpPower,
+            TokenType::TokenTildeSlash => OpCode::OpFloorDivide,
+            TokenType::TokenAmpersand => OpCode::OpBitAnd,
+            TokenType::TokenPipe => OpCode::OpBitOr,
+            TokenType::TokenCaret => OpCode::OpBitXor,
+            TokenType::TokenLessLess => OpCode::OpShiftLeft,
+            TokenType::TokenGreaterGreater => OpCode::OpShiftRight,
+            TokenType::TokenDotDot => OpCode::OpRange,
+            TokenType::TokenDotDotEqual => OpCode::OpRangeInclusive,
+            TokenType::TokenIn => OpCode::OpIn,
+            _ => return,
+        };
+
+        self.emit_byte_at(map_opcode_to_binary(opcode), operator_span);
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+
+use crate::scanner::{Span, Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub token: Token,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str, token: &Token) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            span: token.span,
+            token: token.clone(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // Renders the diagnostic followed by the offending source line with the
+    // token underlined:
+    //
+    //   [line 2] Error at 'print': Expect ';' after value.
+    //       2 | print "never";
+    //         | ^^^^^
+    pub fn render(&self, source: &str) -> String {
+        format!("{}\n{}", self, render_excerpt(source, self.span))
+    }
+}
+
+// Prints the source line the span starts on and underlines the span with
+// carets. Spans running over several lines are underlined up to the end of
+// their first line. Tabs before the span are copied into the padding so the
+// carets stay aligned whatever width the terminal gives a tab.
+pub fn render_excerpt(source: &str, span: Span) -> String {
+    let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[line_start..]
+        .find('\n')
+        .map_or(source.len(), |i| line_start + i);
+    let source_line = source[line_start..line_end].trim_end_matches('\r');
+
+    let underline_end = span.end.clamp(span.start, line_end);
+    let underline_width = source[span.start..underline_end].chars().count().max(1);
+    let gutter = " ".repeat(span.line.to_string().len());
+    let padding: String = source[line_start..span.start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    format!(
+        "    {} | {}\n    {} | {}{}",
+        span.line,
+        source_line,
+        gutter,
+        padding,
+        "^".repeat(underline_width)
+    )
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] {}", self.span.line, self.severity)?;
+
+        match self.token.token_type {
+            TokenType::TokenEof => write!(f, " at end")?,
+            TokenType::TokenError => {}
+            _ => write!(f, " at '{}'", self.token.content)?,
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The span of the first occurrence of `text` in `source`.
+    fn span_of(source: &str, text: &str) -> Span {
+        let start = source.find(text).unwrap();
+        let before = &source[..start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        Span::new(start, start + text.len(), line, column)
+    }
+
+    #[test]
+    fn underlines_the_span() {
+        let source = "var a = 1;\nprint a +;";
+        assert_eq!(
+            render_excerpt(source, span_of(source, "+")),
+            "    2 | print a +;\n      |         ^"
+        );
+    }
+
+    #[test]
+    fn underlines_every_character_of_a_token() {
+        let source = "print missing;";
+        assert_eq!(
+            render_excerpt(source, span_of(source, "missing")),
+            "    1 | print missing;\n      |       ^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn gutter_fits_the_line_number() {
+        let source = format!("{}print x;", "\n".repeat(11));
+        assert_eq!(
+            render_excerpt(&source, span_of(&source, "x")),
+            "    12 | print x;\n       |       ^"
+        );
+    }
+
+    #[test]
+    fn copies_tabs_into_the_padding() {
+        let source = "{\n\t\tprint nil + 1;\n}";
+        assert_eq!(
+            render_excerpt(source, span_of(source, "+")),
+            "    2 | \t\tprint nil + 1;\n      | \t\t          ^"
+        );
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        let source = "print \"héllo\" - 1;";
+        assert_eq!(
+            render_excerpt(source, span_of(source, "-")),
+            "    1 | print \"héllo\" - 1;\n      |               ^"
+        );
+    }
+
+    #[test]
+    fn multi_line_span_stops_at_end_of_line() {
+        let source = "print \"one\ntwo\";";
+        assert_eq!(
+            render_excerpt(source, span_of(source, "\"one\ntwo\"")),
+            "    1 | print \"one\n      |       ^^^^"
+        );
+    }
+
+    #[test]
+    fn empty_span_gets_one_caret() {
+        let source = "print 1";
+        let end = Span::new(source.len(), source.len(), 1, 8);
+        assert_eq!(render_excerpt(source, end), "    1 | print 1\n      |        ^");
+    }
+
+    #[test]
+    fn strips_carriage_return() {
+        let source = "print -nil;\r\nprint 2;";
+        assert_eq!(
+            render_excerpt(source, span_of(source, "-")),
+            "    1 | print -nil;\n      |       ^"
+        );
+    }
+}
@@ -8,30 +8,47 @@ use std::{
 };
 
 use chunk::Chunk;
-use vm::{InterpretResult, VM};
+use vm::{InterpretResult, Options, VM};
 
 mod chunk;
 mod compiler;
+mod diagnostic;
+mod map;
+mod module;
+mod natives;
 mod object;
+mod random;
+mod range;
 mod scanner;
 mod value;
 mod vm;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let argc = args.len();
+    let mut options = Options::default();
+    let mut paths = vec![];
 
-    if argc == 1 {
-        repl();
-    } else if argc == 2 {
-        run_file(&args[1]);
-    } else {
-        println!("Usage: rustLox [path]");
-        exit(64);
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--warn-undeclared-globals" => options.warn_undeclared_globals = true,
+            "--disable-filesystem" => options.disable_filesystem = true,
+            flag if flag.starts_with("--") => usage(),
+            _ => paths.push(arg),
+        }
+    }
+
+    match paths.as_slice() {
+        [] => repl(options),
+        [path] => run_file(path, options),
+        _ => usage(),
     }
 }
 
-fn run_file(path: &str) {
+fn usage() -> ! {
+    println!("Usage: rustLox [--warn-undeclared-globals] [--disable-filesystem] [path]");
+    exit(64);
+}
+
+fn run_file(path: &str, options: Options) {
     let read_result = fs::read_to_string(path);
     if read_result.is_err() {
         println!(
@@ -42,7 +59,7 @@ fn run_file(path: &str) {
         exit(74);
     }
     let str = read_result.unwrap();
-    let result: InterpretResult = interpret(&str);
+    let result: InterpretResult = interpret(&str, options);
     match result {
         InterpretResult::InterpretCompileError => {
             exit(65);
@@ -54,7 +71,7 @@ fn run_file(path: &str) {
     }
 }
 
-fn repl() {
+fn repl(options: Options) {
     loop {
         print!("> ");
         let _ = io::stdout().flush();
@@ -64,14 +81,15 @@ fn repl() {
             println!("error reading line");
             break;
         } else {
-            interpret(&s);
+            interpret(&s, options);
         }
     }
 }
 
-fn interpret(code: &str) -> InterpretResult {
+fn interpret(code: &str, options: Options) -> InterpretResult {
     let chunk = Chunk::new();
     let mut vm = VM::new(chunk);
+    vm.set_options(options);
     let result = vm.interpret(code);
     //vm.dump_stats();
     result
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use generational_arena::Index;
+
+use crate::{object::Object, value::Value};
+
+// The hashable form of a map key. Strings are interned, so the index of
+// their text identifies them just as Lox equality does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MapKey {
+    Nil,
+    Boolean(bool),
+    // The bits of the number, with -0 folded into 0 since they are equal.
+    Number(u64),
+    String(Index),
+}
+
+impl MapKey {
+    fn from_value(value: &Value) -> Result<MapKey, String> {
+        match value {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Boolean(b) => Ok(MapKey::Boolean(*b)),
+            Value::Number(n) if n.is_nan() => Err("Map key can't be NaN.".into()),
+            Value::Number(n) if *n == 0.0 => Ok(MapKey::Number(0f64.to_bits())),
+            Value::Number(n) => Ok(MapKey::Number(n.to_bits())),
+            Value::Object(_, Object::ObjString(string)) => Ok(MapKey::String(*string.id())),
+            Value::Object(_, _) => {
+                Err("Map keys must be strings, numbers, booleans or nil.".into())
+            }
+        }
+    }
+}
+
+// A hash map that remembers insertion order, so printing and iterating a
+// map always visit its keys in the order they were first added.
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    slots: HashMap<MapKey, usize>,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<&Value>, String> {
+        let slot = self.slots.get(&MapKey::from_value(key)?);
+        Ok(slot.map(|&slot| &self.entries[slot].1))
+    }
+
+    pub fn contains(&self, key: &Value) -> Result<bool, String> {
+        Ok(self.slots.contains_key(&MapKey::from_value(key)?))
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<(), String> {
+        let map_key = MapKey::from_value(&key)?;
+        match self.slots.get(&map_key) {
+            Some(&slot) => self.entries[slot].1 = value,
+            None => {
+                self.slots.insert(map_key, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the removed value. The entries after it move down a slot to
+    // keep the insertion order.
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>, String> {
+        let slot = match self.slots.remove(&MapKey::from_value(key)?) {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+
+        let (_, value) = self.entries.remove(slot);
+        for later in self.slots.values_mut() {
+            if *later > slot {
+                *later -= 1;
+            }
+        }
+        Ok(Some(value))
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+use crate::value::Value;
+
+// A namespace of natives and constants, such as `math`, read with the
+// property syntax: `math.pi`, `math.sqrt(2)`.
+pub struct Module {
+    pub name: &'static str,
+    members: HashMap<String, Value>,
+}
+
+impl Module {
+    pub fn new(name: &'static str) -> Module {
+        Module {
+            name,
+            members: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.members.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, value: Value) {
+        self.members.insert(name.into(), value);
+    }
+}
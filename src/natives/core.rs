@@ -0,0 +1,95 @@
+use crate::{
+    object::Object,
+    value::{format_value, Value},
+    vm::{resolve_index, VM},
+};
+
+use super::{Arity, Receiver};
+
+pub fn define(vm: &mut VM) {
+    vm.define_native("len", Arity::Exact(1), len);
+    vm.define_native("remove", Arity::Exact(2), remove);
+    vm.define_native("str", Arity::Exact(1), str);
+    vm.define_native("num", Arity::Exact(1), num);
+
+    for receiver in [Receiver::String, Receiver::List, Receiver::Map, Receiver::Range] {
+        vm.define_property(receiver, "len", len);
+    }
+    vm.define_method(Receiver::List, "remove", Arity::Exact(1), remove);
+    vm.define_method(Receiver::Map, "remove", Arity::Exact(1), remove);
+}
+
+// Strings count their characters.
+fn len(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let length = match &args[0] {
+        Value::Object(_, Object::ObjString(string)) => vm.string(string).chars().count(),
+        Value::Object(_, Object::ObjList(list)) => vm.list(list).len(),
+        Value::Object(_, Object::ObjMap(map)) => vm.map(map).len(),
+        Value::Object(_, Object::ObjRange(range)) => {
+            return Ok(Value::Number(vm.range(range).len()));
+        }
+        _ => return Err("Argument 1 to 'len' must be a string, list, map or range.".into()),
+    };
+    Ok(Value::Number(length as f64))
+}
+
+// The text `print` would show for the value.
+fn str(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = format_value(&args[0], vm);
+    Ok(vm.get_or_create_string_object(&text))
+}
+
+// Parses a decimal number, optionally signed and with an exponent, from a
+// string. Surrounding whitespace is ignored.
+fn num(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = match &args[0] {
+        Value::Number(n) => return Ok(Value::Number(*n)),
+        Value::Object(_, Object::ObjString(string)) => vm.string(string),
+        _ => return Err("Argument 1 to 'num' must be a string or number.".into()),
+    };
+
+    match parse_number(text.trim()) {
+        Some(number) => Ok(Value::Number(number)),
+        None => Err(format!("Can't convert '{}' to a number.", text)),
+    }
+}
+
+// Rust's float parser also takes spellings such as "inf", "NaN" and ".5"
+// that aren't numbers in Lox, so the text is checked first.
+fn parse_number(text: &str) -> Option<f64> {
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(position) => (&unsigned[..position], Some(&unsigned[position + 1..])),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let valid = is_digits(integer)
+        && fraction.is_none_or(is_digits)
+        && exponent.is_none_or(|e| is_digits(e.strip_prefix(['-', '+']).unwrap_or(e)));
+
+    if !valid {
+        return None;
+    }
+    text.parse().ok()
+}
+
+// Removes the element at an index of a list or the entry for a key of a
+// map, and returns its value. Removing a missing key returns nil.
+fn remove(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Object(_, Object::ObjList(list)) => {
+            let elements = vm.list_mut(list);
+            let index = resolve_index(&args[1], elements.len(), "List")?;
+            Ok(elements.remove(index))
+        }
+        Value::Object(_, Object::ObjMap(map)) => {
+            Ok(vm.map_mut(map).remove(&args[1])?.unwrap_or_default())
+        }
+        _ => Err("Argument 1 to 'remove' must be a list or map.".into()),
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+};
+
+use crate::{value::Value, vm::VM};
+
+use super::{string_arg, Arity};
+
+// A read or write that fails returns nil instead of its result, and
+// `io_error()` returns why, such as "Can't read file 'notes.txt': No such
+// file or directory.", until the next I/O native is called. Arguments of
+// the wrong type and disabled filesystem access are still runtime errors.
+
+pub fn define(vm: &mut VM) {
+    vm.define_native("read_file", Arity::Exact(1), read_file);
+    vm.define_native("write_file", Arity::Exact(2), write_file);
+    vm.define_native("append_file", Arity::Exact(2), append_file);
+    vm.define_native("file_exists", Arity::Exact(1), file_exists);
+    vm.define_native("list_dir", Arity::Exact(1), list_dir);
+    vm.define_native("read_line", Arity::Exact(0), read_line);
+    vm.define_native("read_all_stdin", Arity::Exact(0), read_all_stdin);
+    vm.define_native("io_error", Arity::Exact(0), io_error);
+}
+
+// Every filesystem native checks this first. Reading stdin is still
+// allowed when the filesystem is disabled.
+fn check_filesystem_access(vm: &VM) -> Result<(), String> {
+    if vm.options().disable_filesystem {
+        return Err("Filesystem access is disabled.".into());
+    }
+    Ok(())
+}
+
+// Records why an I/O native failed and returns the nil it hands back.
+fn failure(vm: &mut VM, action: &str, path: &str, error: io::Error) -> Value {
+    let text = error.to_string();
+    let reason = text.split(" (os error").next().unwrap_or(&text);
+    let message = format!("Can't {} '{}': {}.", action, path, reason);
+    vm.set_io_error(Some(message));
+    Value::Nil
+}
+
+fn path_arg(vm: &VM, args: &[Value], position: usize, name: &str) -> Result<String, String> {
+    let path = string_arg(args, position, name)?;
+    Ok(vm.string(&path).to_string())
+}
+
+fn read_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    check_filesystem_access(vm)?;
+    let path = path_arg(vm, args, 1, "read_file")?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(vm.get_or_create_string_object(&contents)),
+        Err(e) => Ok(failure(vm, "read file", &path, e)),
+    }
+}
+
+// Creates the file, or replaces what it held. Returns true once written.
+fn write_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    check_filesystem_access(vm)?;
+    let path = path_arg(vm, args, 1, "write_file")?;
+    let text = string_arg(args, 2, "write_file")?;
+    match fs::write(&path, vm.string(&text)) {
+        Ok(()) => Ok(Value::Boolean(true)),
+        Err(e) => Ok(failure(vm, "write file", &path, e)),
+    }
+}
+
+// Creates the file if it doesn't exist. Returns true once written.
+fn append_file(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    check_filesystem_access(vm)?;
+    let path = path_arg(vm, args, 1, "append_file")?;
+    let text = string_arg(args, 2, "append_file")?;
+    let appended = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(vm.string(&text).as_bytes()));
+    match appended {
+        Ok(()) => Ok(Value::Boolean(true)),
+        Err(e) => Ok(failure(vm, "append to file", &path, e)),
+    }
+}
+
+// True for directories too.
+fn file_exists(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    check_filesystem_access(vm)?;
+    let path = path_arg(vm, args, 1, "file_exists")?;
+    Ok(Value::Boolean(fs::exists(&path).unwrap_or(false)))
+}
+
+// The names of the entries in a directory, sorted.
+fn list_dir(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    check_filesystem_access(vm)?;
+    let path = path_arg(vm, args, 1, "list_dir")?;
+    let names = fs::read_dir(&path).and_then(|entries| {
+        entries
+            .map(|entry| entry.map(|e| e.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<String>, io::Error>>()
+    });
+    let mut names = match names {
+        Ok(names) => names,
+        Err(e) => return Ok(failure(vm, "list directory", &path, e)),
+    };
+    names.sort();
+
+    let names = names
+        .iter()
+        .map(|name| vm.get_or_create_string_object(name))
+        .collect();
+    Ok(vm.new_list(names))
+}
+
+// The next line of stdin without its line ending, or nil at the end of the
+// input.
+fn read_line(vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) => return Ok(Value::Nil),
+        Ok(_) => {}
+        Err(e) => return Ok(failure(vm, "read from", "stdin", e)),
+    }
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    Ok(vm.get_or_create_string_object(line))
+}
+
+// Everything left on stdin, up to the end of the input.
+fn read_all_stdin(vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    vm.set_io_error(None);
+    let mut input = String::new();
+    match io::stdin().read_to_string(&mut input) {
+        Ok(_) => Ok(vm.get_or_create_string_object(&input)),
+        Err(e) => Ok(failure(vm, "read from", "stdin", e)),
+    }
+}
+
+// Why the last I/O native failed, or nil if it succeeded.
+fn io_error(vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    match vm.io_error().map(String::from) {
+        Some(message) => Ok(vm.get_or_create_string_object(&message)),
+        None => Ok(Value::Nil),
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{value::Value, vm::VM};
+
+use super::{integer_arg, list_arg, Arity, Receiver};
+
+pub fn define(vm: &mut VM) {
+    vm.define_native("push", Arity::Exact(2), push);
+    vm.define_native("pop", Arity::Exact(1), pop);
+    vm.define_native("insert", Arity::Exact(3), insert);
+    vm.define_native("slice", Arity::Range(2, 3), slice);
+
+    vm.define_method(Receiver::List, "push", Arity::Exact(1), push);
+    vm.define_method(Receiver::List, "pop", Arity::Exact(0), pop);
+    vm.define_method(Receiver::List, "insert", Arity::Exact(2), insert);
+    vm.define_method(Receiver::List, "slice", Arity::Range(1, 2), slice);
+}
+
+// Like an index, but `length` itself is allowed: it is the position after
+// the last element.
+fn position(index: i64, length: usize) -> Result<usize, String> {
+    let resolved = if index < 0 { index + length as i64 } else { index };
+    if resolved < 0 || resolved > length as i64 {
+        return Err(format!(
+            "Insert position {} out of bounds for length {}.",
+            index, length
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+// Slice bounds are clamped to the list, so `slice(xs, 0, 100)` copies it.
+fn clamp(index: i64, length: usize) -> usize {
+    let resolved = if index < 0 { index + length as i64 } else { index };
+    resolved.clamp(0, length as i64) as usize
+}
+
+fn push(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let list = list_arg(args, 1, "push")?;
+    vm.list_mut(&list).push(args[1].clone());
+    Ok(Value::Nil)
+}
+
+fn pop(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let list = list_arg(args, 1, "pop")?;
+    vm.list_mut(&list)
+        .pop()
+        .ok_or_else(|| "Can't pop from an empty list.".into())
+}
+
+fn insert(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let list = list_arg(args, 1, "insert")?;
+    let index = integer_arg(args, 2, "insert")?;
+    let elements = vm.list_mut(&list);
+    let index = position(index, elements.len())?;
+    elements.insert(index, args[2].clone());
+    Ok(Value::Nil)
+}
+
+fn slice(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let list = list_arg(args, 1, "slice")?;
+    let elements = vm.list(&list);
+    let start = clamp(integer_arg(args, 2, "slice")?, elements.len());
+    let end = match args.get(2) {
+        Some(_) => clamp(integer_arg(args, 3, "slice")?, elements.len()),
+        None => elements.len(),
+    };
+
+    let copied = elements[start..end.max(start)].to_vec();
+    Ok(vm.new_list(copied))
+}
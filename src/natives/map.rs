@@ -0,0 +1,31 @@
+use crate::{value::Value, vm::VM};
+
+use super::{map_arg, Arity, Receiver};
+
+pub fn define(vm: &mut VM) {
+    vm.define_native("has", Arity::Exact(2), has);
+    vm.define_native("keys", Arity::Exact(1), keys);
+    vm.define_native("values", Arity::Exact(1), values);
+
+    vm.define_method(Receiver::Map, "has", Arity::Exact(1), has);
+    vm.define_method(Receiver::Map, "keys", Arity::Exact(0), keys);
+    vm.define_method(Receiver::Map, "values", Arity::Exact(0), values);
+}
+
+fn has(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let map = map_arg(args, 1, "has")?;
+    Ok(Value::Boolean(vm.map(&map).contains(&args[1])?))
+}
+
+// Both return a new list, in the map's insertion order.
+fn keys(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let map = map_arg(args, 1, "keys")?;
+    let keys = vm.map(&map).entries().iter().map(|(key, _)| key.clone()).collect();
+    Ok(vm.new_list(keys))
+}
+
+fn values(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let map = map_arg(args, 1, "values")?;
+    let values = vm.map(&map).entries().iter().map(|(_, value)| value.clone()).collect();
+    Ok(vm.new_list(values))
+}
@@ -0,0 +1,124 @@
+use std::f64::consts::PI;
+
+use crate::{value::Value, vm::VM};
+
+use super::{number_arg, Arity};
+
+pub fn define(vm: &mut VM) {
+    let math = vm.define_module("math");
+
+    vm.define_module_value(&math, "pi", Value::Number(PI));
+    vm.define_module_value(&math, "inf", Value::Number(f64::INFINITY));
+    vm.define_module_value(&math, "nan", Value::Number(f64::NAN));
+
+    vm.define_module_native(&math, "sqrt", Arity::Exact(1), sqrt);
+    vm.define_module_native(&math, "pow", Arity::Exact(2), pow);
+    vm.define_module_native(&math, "floor", Arity::Exact(1), floor);
+    vm.define_module_native(&math, "ceil", Arity::Exact(1), ceil);
+    vm.define_module_native(&math, "round", Arity::Exact(1), round);
+    vm.define_module_native(&math, "abs", Arity::Exact(1), abs);
+    vm.define_module_native(&math, "min", Arity::Range(1, 255), min);
+    vm.define_module_native(&math, "max", Arity::Range(1, 255), max);
+    vm.define_module_native(&math, "sin", Arity::Exact(1), sin);
+    vm.define_module_native(&math, "cos", Arity::Exact(1), cos);
+    vm.define_module_native(&math, "tan", Arity::Exact(1), tan);
+    vm.define_module_native(&math, "atan2", Arity::Exact(2), atan2);
+    vm.define_module_native(&math, "log", Arity::Exact(1), log);
+    vm.define_module_native(&math, "exp", Arity::Exact(1), exp);
+    vm.define_module_native(&math, "is_nan", Arity::Exact(1), is_nan);
+    vm.define_module_native(&math, "clamp", Arity::Exact(3), clamp);
+}
+
+fn sqrt(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "sqrt")?.sqrt()))
+}
+
+fn pow(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let base = number_arg(args, 1, "pow")?;
+    let exponent = number_arg(args, 2, "pow")?;
+    Ok(Value::Number(base.powf(exponent)))
+}
+
+fn floor(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "floor")?.floor()))
+}
+
+fn ceil(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "ceil")?.ceil()))
+}
+
+// Halves round away from zero: round(2.5) is 3, round(-2.5) is -3.
+fn round(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "round")?.round()))
+}
+
+fn abs(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "abs")?.abs()))
+}
+
+// min and max take any number of arguments. A NaN argument makes the
+// result NaN.
+fn min(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    fold_numbers(args, "min", |a, b| if a < b { a } else { b })
+}
+
+fn max(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    fold_numbers(args, "max", |a, b| if a > b { a } else { b })
+}
+
+fn fold_numbers(args: &[Value], name: &str, pick: fn(f64, f64) -> f64) -> Result<Value, String> {
+    let mut result = number_arg(args, 1, name)?;
+    for position in 2..=args.len() {
+        let number = number_arg(args, position, name)?;
+        result = if result.is_nan() || number.is_nan() {
+            f64::NAN
+        } else {
+            pick(result, number)
+        };
+    }
+    Ok(Value::Number(result))
+}
+
+fn sin(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "sin")?.sin()))
+}
+
+fn cos(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "cos")?.cos()))
+}
+
+fn tan(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "tan")?.tan()))
+}
+
+// The angle of the point (x, y), called as atan2(y, x) like in C.
+fn atan2(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let y = number_arg(args, 1, "atan2")?;
+    let x = number_arg(args, 2, "atan2")?;
+    Ok(Value::Number(y.atan2(x)))
+}
+
+// The natural logarithm.
+fn log(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "log")?.ln()))
+}
+
+fn exp(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number_arg(args, 1, "exp")?.exp()))
+}
+
+// Plain `x != x` also finds NaN, the only number not equal to itself.
+fn is_nan(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Boolean(number_arg(args, 1, "is_nan")?.is_nan()))
+}
+
+fn clamp(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let number = number_arg(args, 1, "clamp")?;
+    let low = number_arg(args, 2, "clamp")?;
+    let high = number_arg(args, 3, "clamp")?;
+    // f64::clamp panics on these, including NaN bounds.
+    if low.is_nan() || high.is_nan() || low > high {
+        return Err("Clamp bounds must be in order.".into());
+    }
+    Ok(Value::Number(number.clamp(low, high)))
+}
@@ -0,0 +1,150 @@
+// Functions implemented in Rust and exposed to scripts as globals, or as
+// methods and properties of the built-in object types. Each library module
+// registers its natives on the VM in `define`.
+
+mod core;
+mod io;
+mod list;
+mod map;
+mod math;
+mod random;
+mod range;
+mod string;
+
+use crate::{
+    object::{Object, ObjectList, ObjectMap, ObjectRange, ObjectString},
+    value::Value,
+    vm::VM,
+};
+
+// Natives get the VM, for allocating and reading objects, and their
+// arguments. An `Err` becomes a runtime error at the call site.
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+}
+
+impl Arity {
+    pub fn check(&self, arg_count: usize) -> Result<(), String> {
+        match *self {
+            Arity::Exact(arity) if arg_count != arity => Err(format!(
+                "Expected {} {} but got {}.",
+                arity,
+                plural(arity, "argument"),
+                arg_count
+            )),
+            Arity::Range(min, max) if arg_count < min || arg_count > max => Err(format!(
+                "Expected {} to {} arguments but got {}.",
+                min, max, arg_count
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        word.into()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub function: NativeFn,
+}
+
+// The object types that have methods and properties. A method native
+// receives the receiver as its first argument, followed by the arguments
+// of the call, which is all `Arity` counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Receiver {
+    String,
+    List,
+    Map,
+    Range,
+}
+
+impl Receiver {
+    pub fn of(value: &Value) -> Option<Receiver> {
+        match value {
+            Value::Object(_, Object::ObjString(_)) => Some(Receiver::String),
+            Value::Object(_, Object::ObjList(_)) => Some(Receiver::List),
+            Value::Object(_, Object::ObjMap(_)) => Some(Receiver::Map),
+            Value::Object(_, Object::ObjRange(_)) => Some(Receiver::Range),
+            _ => None,
+        }
+    }
+}
+
+pub fn define_natives(vm: &mut VM) {
+    core::define(vm);
+    io::define(vm);
+    list::define(vm);
+    map::define(vm);
+    math::define(vm);
+    random::define(vm);
+    range::define(vm);
+    string::define(vm);
+}
+
+// Argument helpers. `position` counts from 1, as in the error messages.
+
+fn argument_error(name: &str, position: usize, kind: &str) -> String {
+    format!("Argument {} to '{}' must be {}.", position, name, kind)
+}
+
+pub fn string_arg(args: &[Value], position: usize, name: &str) -> Result<ObjectString, String> {
+    match &args[position - 1] {
+        Value::Object(_, Object::ObjString(string)) => Ok(string.clone()),
+        _ => Err(argument_error(name, position, "a string")),
+    }
+}
+
+pub fn list_arg(args: &[Value], position: usize, name: &str) -> Result<ObjectList, String> {
+    match &args[position - 1] {
+        Value::Object(_, Object::ObjList(list)) => Ok(list.clone()),
+        _ => Err(argument_error(name, position, "a list")),
+    }
+}
+
+pub fn map_arg(args: &[Value], position: usize, name: &str) -> Result<ObjectMap, String> {
+    match &args[position - 1] {
+        Value::Object(_, Object::ObjMap(map)) => Ok(map.clone()),
+        _ => Err(argument_error(name, position, "a map")),
+    }
+}
+
+pub fn range_arg(args: &[Value], position: usize, name: &str) -> Result<ObjectRange, String> {
+    match &args[position - 1] {
+        Value::Object(_, Object::ObjRange(range)) => Ok(range.clone()),
+        _ => Err(argument_error(name, position, "a range")),
+    }
+}
+
+pub fn number_arg(args: &[Value], position: usize, name: &str) -> Result<f64, String> {
+    match args[position - 1] {
+        Value::Number(n) => Ok(n),
+        _ => Err(argument_error(name, position, "a number")),
+    }
+}
+
+// Integers that don't fit in an i64 are errors rather than being clamped.
+pub fn integer_arg(args: &[Value], position: usize, name: &str) -> Result<i64, String> {
+    match args[position - 1] {
+        Value::Number(n) if n.fract() == 0.0 && n.is_finite() => {
+            // 2^63 is the first value past i64::MAX that an f64 can hold.
+            if n < i64::MIN as f64 || n >= 9223372036854775808.0 {
+                return Err(argument_error(name, position, "an integer in range"));
+            }
+            Ok(n as i64)
+        }
+        _ => Err(argument_error(name, position, "an integer")),
+    }
+}
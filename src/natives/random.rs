@@ -0,0 +1,61 @@
+use crate::{random::Rng, value::Value, vm::VM};
+
+use super::{integer_arg, list_arg, Arity};
+
+pub fn define(vm: &mut VM) {
+    vm.define_native("random", Arity::Exact(0), random);
+    vm.define_native("random_int", Arity::Exact(2), random_int);
+    vm.define_native("choice", Arity::Exact(1), choice);
+    vm.define_native("shuffle", Arity::Exact(1), shuffle);
+    vm.define_native("seed", Arity::Exact(1), seed);
+}
+
+// A number in [0, 1).
+fn random(vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(vm.rng().next_f64()))
+}
+
+// An integer from `low` to `high`, both included.
+fn random_int(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let low = integer_arg(args, 1, "random_int")?;
+    let high = integer_arg(args, 2, "random_int")?;
+    if low > high {
+        return Err("Random bounds must be in order.".into());
+    }
+
+    let count = (high as i128 - low as i128 + 1) as u128;
+    let offset = if count > u64::MAX as u128 {
+        vm.rng().next_u64()
+    } else {
+        vm.rng().below(count as u64)
+    };
+    Ok(Value::Number((low as i128 + offset as i128) as f64))
+}
+
+fn choice(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let list = list_arg(args, 1, "choice")?;
+    let length = vm.list(&list).len();
+    if length == 0 {
+        return Err("Can't choose from an empty list.".into());
+    }
+
+    let index = vm.rng().below(length as u64) as usize;
+    Ok(vm.list(&list)[index].clone())
+}
+
+// Shuffles the list in place with a Fisher-Yates shuffle.
+fn shuffle(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let list = list_arg(args, 1, "shuffle")?;
+    for i in (1..vm.list(&list).len()).rev() {
+        let j = vm.rng().below(i as u64 + 1) as usize;
+        vm.list_mut(&list).swap(i, j);
+    }
+    Ok(Value::Nil)
+}
+
+// Restarts the sequence: the same seed gives the same numbers.
+fn seed(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let seed = integer_arg(args, 1, "seed")?;
+    *vm.rng() = Rng::new(seed as u64);
+    Ok(Value::Nil)
+}
@@ -0,0 +1,22 @@
+use crate::{value::Value, vm::VM};
+
+use super::{number_arg, range_arg, Arity, Receiver};
+
+pub fn define(vm: &mut VM) {
+    vm.define_native("step", Arity::Exact(2), step);
+    vm.define_method(Receiver::Range, "step", Arity::Exact(1), step);
+}
+
+// `step(0..10, 2)` is a copy of the range counting in twos. A negative
+// step counts down: `step(10..0, -1)`.
+fn step(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let range = range_arg(args, 1, "step")?;
+    let step = number_arg(args, 2, "step")?;
+    if step == 0.0 || step.is_nan() {
+        return Err("Range step must be a non-zero number.".into());
+    }
+
+    let mut stepped = *vm.range(&range);
+    stepped.step = step;
+    Ok(vm.new_range(stepped))
+}
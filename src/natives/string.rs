@@ -0,0 +1,138 @@
+use crate::{
+    object::Object,
+    value::{format_value, Value},
+    vm::VM,
+};
+
+use super::{integer_arg, list_arg, string_arg, Arity, Receiver};
+
+// Longest string, in bytes, that `repeat` builds. Without a limit a large
+// count aborts the process when the allocation fails.
+const MAX_REPEAT_LENGTH: usize = 1 << 28;
+
+pub fn define(vm: &mut VM) {
+    vm.define_method(Receiver::String, "split", Arity::Range(0, 1), split);
+    vm.define_method(Receiver::String, "join", Arity::Exact(1), join);
+    vm.define_method(Receiver::String, "trim", Arity::Exact(0), trim);
+    vm.define_method(Receiver::String, "upper", Arity::Exact(0), upper);
+    vm.define_method(Receiver::String, "lower", Arity::Exact(0), lower);
+    vm.define_method(Receiver::String, "find", Arity::Exact(1), find);
+    vm.define_method(Receiver::String, "replace", Arity::Exact(2), replace);
+    vm.define_method(Receiver::String, "starts_with", Arity::Exact(1), starts_with);
+    vm.define_method(Receiver::String, "ends_with", Arity::Exact(1), ends_with);
+    vm.define_method(Receiver::String, "repeat", Arity::Exact(1), repeat);
+    vm.define_method(Receiver::String, "chars", Arity::Exact(0), chars);
+}
+
+// The text of the receiver. Methods are only looked up on strings, so the
+// first argument always is one.
+fn receiver(vm: &VM, args: &[Value]) -> String {
+    match &args[0] {
+        Value::Object(_, Object::ObjString(string)) => vm.string(string).to_string(),
+        _ => unreachable!("string method called on a non-string receiver"),
+    }
+}
+
+// The text of the argument at `position`, counting the receiver as 0.
+fn text_arg(vm: &VM, args: &[Value], position: usize, name: &str) -> Result<String, String> {
+    let string = string_arg(&args[1..], position, name)?;
+    Ok(vm.string(&string).to_string())
+}
+
+fn new_list_of_strings<'a>(vm: &mut VM, parts: impl Iterator<Item = &'a str>) -> Value {
+    let strings = parts
+        .map(|part| vm.get_or_create_string_object(part))
+        .collect();
+    vm.new_list(strings)
+}
+
+// Without a separator, splits on runs of whitespace.
+fn split(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    if args.len() == 1 {
+        return Ok(new_list_of_strings(vm, text.split_whitespace()));
+    }
+
+    let separator = text_arg(vm, args, 1, "split")?;
+    if separator.is_empty() {
+        return Err("Separator can't be empty.".into());
+    }
+    Ok(new_list_of_strings(vm, text.split(separator.as_str())))
+}
+
+// `", ".join(parts)` formats each element as `print` would.
+fn join(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let separator = receiver(vm, args);
+    let list = list_arg(&args[1..], 1, "join")?;
+    let parts: Vec<String> = vm
+        .list(&list)
+        .iter()
+        .map(|element| format_value(element, vm))
+        .collect();
+    Ok(vm.get_or_create_string_object(&parts.join(&separator)))
+}
+
+fn trim(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    Ok(vm.get_or_create_string_object(text.trim()))
+}
+
+fn upper(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    Ok(vm.get_or_create_string_object(&text.to_uppercase()))
+}
+
+fn lower(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    Ok(vm.get_or_create_string_object(&text.to_lowercase()))
+}
+
+// The character index of the first occurrence, or -1.
+fn find(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    let needle = text_arg(vm, args, 1, "find")?;
+    let index = match text.find(needle.as_str()) {
+        Some(offset) => text[..offset].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(Value::Number(index))
+}
+
+// Replaces every occurrence.
+fn replace(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    let from = text_arg(vm, args, 1, "replace")?;
+    let to = text_arg(vm, args, 2, "replace")?;
+    Ok(vm.get_or_create_string_object(&text.replace(from.as_str(), &to)))
+}
+
+fn starts_with(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    let prefix = text_arg(vm, args, 1, "starts_with")?;
+    Ok(Value::Boolean(text.starts_with(prefix.as_str())))
+}
+
+fn ends_with(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    let suffix = text_arg(vm, args, 1, "ends_with")?;
+    Ok(Value::Boolean(text.ends_with(suffix.as_str())))
+}
+
+fn repeat(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    let count = integer_arg(&args[1..], 1, "repeat")?;
+    if count < 0 {
+        return Err("Repeat count can't be negative.".into());
+    }
+    let length = text.len().checked_mul(count as usize);
+    if length.is_none_or(|length| length > MAX_REPEAT_LENGTH) {
+        return Err("Repeat result too large.".into());
+    }
+    Ok(vm.get_or_create_string_object(&text.repeat(count as usize)))
+}
+
+fn chars(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let text = receiver(vm, args);
+    let chars: Vec<String> = text.chars().map(String::from).collect();
+    Ok(new_list_of_strings(vm, chars.iter().map(String::as_str)))
+}
@@ -1,35 +1,129 @@
-use generational_arena::Index;
-
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct ObjectString {
-    id: Index,
-}
-
-impl ObjectString {
-    pub fn new(index: Index) -> ObjectString {
-        ObjectString { id: index }
-    }
-
-    pub fn id(&self) -> &Index {
-        &self.id
-    }
-}
-
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum Object {
-    ObjString(ObjectString),
-}
-
-impl Object {
-    pub fn is_string(&self) -> bool {
-        match &self {
-            Self::ObjString(_) => true,
-        }
-    }
-
-    pub fn as_string(&self) -> &ObjectString {
-        match &self {
-            Self::ObjString(a) => return a,
-        }
-    }
-}
+#![allow(
+    clippy::needless_return,
+    clippy::match_like_matches_macro
+)]
+
+use generational_arena::Index;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ObjectString {
+    id: Index,
+}
+
+impl ObjectString {
+    pub fn new(index: Index) -> ObjectString {
+        ObjectString { id: index }
+    }
+
+    pub fn id(&self) -> &Index {
+        &self.id
+    }
+}
+
+// The elements live in the VM's list arena so that every value referring
+// to the list sees its mutations.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ObjectList {
+    id: Index,
+}
+
+impl ObjectList {
+    pub fn new(index: Index) -> ObjectList {
+        ObjectList { id: index }
+    }
+
+    pub fn id(&self) -> &Index {
+        &self.id
+    }
+}
+
+// Like lists, the entries live in an arena in the VM.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ObjectMap {
+    id: Index,
+}
+
+impl ObjectMap {
+    pub fn new(index: Index) -> ObjectMap {
+        ObjectMap { id: index }
+    }
+
+    pub fn id(&self) -> &Index {
+        &self.id
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ObjectRange {
+    id: Index,
+}
+
+impl ObjectRange {
+    pub fn new(index: Index) -> ObjectRange {
+        ObjectRange { id: index }
+    }
+
+    pub fn id(&self) -> &Index {
+        &self.id
+    }
+}
+
+// A namespace of natives, like lists kept in an arena in the VM.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ObjectModule {
+    id: Index,
+}
+
+impl ObjectModule {
+    pub fn new(index: Index) -> ObjectModule {
+        ObjectModule { id: index }
+    }
+
+    pub fn id(&self) -> &Index {
+        &self.id
+    }
+}
+
+// A function implemented in Rust, identified by its slot in the VM's
+// native registry.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ObjectNative {
+    id: usize,
+}
+
+impl ObjectNative {
+    pub fn new(id: usize) -> ObjectNative {
+        ObjectNative { id }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Object {
+    ObjString(ObjectString),
+    ObjList(ObjectList),
+    ObjMap(ObjectMap),
+    ObjRange(ObjectRange),
+    ObjModule(ObjectModule),
+    ObjNative(ObjectNative),
+}
+
+impl Object {
+    pub fn is_string(&self) -> bool {
+        match &self {
+            Self::ObjString(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn as_string(&self) -> &ObjectString {
+        match &self {
+            Self::ObjString(a) => return a,
+            _ => panic!("try to cast a non string object"),
+        }
+    }
+}
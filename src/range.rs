@@ -0,0 +1,87 @@
+// The numbers from `start` towards `end` in increments of `step`, as built
+// by `start..end` and `start..=end`. `end` itself is included only in
+// inclusive ranges.
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: f64, end: f64, inclusive: bool) -> Range {
+        Range {
+            start,
+            end,
+            step: 1.0,
+            inclusive,
+        }
+    }
+
+    // A float, so that ranges too long to count in a usize still report
+    // their length.
+    pub fn len(&self) -> f64 {
+        let span = (self.end - self.start) / self.step;
+        let count = if self.inclusive {
+            span.floor() + 1.0
+        } else {
+            span.ceil()
+        };
+
+        if count.is_nan() || count < 0.0 {
+            return 0.0;
+        }
+        count
+    }
+
+    pub fn get(&self, index: usize) -> Option<f64> {
+        if index as f64 >= self.len() {
+            return None;
+        }
+        Some(self.start + index as f64 * self.step)
+    }
+
+    // Compares against the bounds directly rather than counting steps, so
+    // that huge ranges still contain their ends.
+    pub fn contains(&self, number: f64) -> bool {
+        let before_end = if self.step > 0.0 {
+            number < self.end
+        } else {
+            number > self.end
+        };
+        let after_start = if self.step > 0.0 {
+            number >= self.start
+        } else {
+            number <= self.start
+        };
+        let on_step = ((number - self.start) / self.step).fract() == 0.0;
+        after_start && (before_end || (self.inclusive && number == self.end)) && on_step
+    }
+
+    // The indices this range selects from a sequence of `length` elements.
+    // Negative bounds count from the end, and bounds past either end are
+    // clamped, as with `slice`.
+    pub fn slice_indices(&self, length: usize) -> Result<Vec<usize>, String> {
+        let is_integer = |n: f64| n.fract() == 0.0 && n.is_finite();
+        if !is_integer(self.start) || !is_integer(self.end) {
+            return Err("Slice bounds must be integers.".into());
+        }
+        if !is_integer(self.step) || self.step < 1.0 {
+            return Err("Slice step must be a positive integer.".into());
+        }
+
+        // Bounds may be far outside any length, so resolve them as floats.
+        let resolve = |bound: f64| {
+            let resolved = if bound < 0.0 { bound + length as f64 } else { bound };
+            resolved.clamp(0.0, length as f64) as usize
+        };
+        let start = resolve(self.start);
+        let mut end = resolve(self.end);
+        if self.inclusive && self.end + length as f64 >= 0.0 {
+            end = (end + 1).min(length);
+        }
+
+        Ok((start..end.max(start)).step_by(self.step as usize).collect())
+    }
+}
@@ -1,401 +1,912 @@
-pub struct Scanner {
-    line: usize,
-    source: String,
-    start: usize,
-    current: usize,
-}
-
-#[derive(Debug, Clone)]
-pub struct Token {
-    pub token_type: TokenType,
-    start: usize,
-    length: usize,
-    pub line: usize,
-    pub content: String,
-}
-
-impl Token {
-    pub const fn new_dummy_token() -> Token {
-        Token {
-            token_type: TokenType::TokenNumber,
-            start: 0,
-            length: 0,
-            line: 0,
-            content: String::new(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum TokenType {
-    TokenLeftParen,
-    TokenRightParen,
-    TokenLeftBrace,
-    TokenRightBrace,
-    TokenComma,
-    TokenDot,
-    TokenMinus,
-    TokenPlus,
-    TokenSemicolon,
-    TokenSlash,
-    TokenStar,
-    TokenBang,
-    TokenBangEqual,
-    TokenEqual,
-    TokenEqualEqual,
-    TokenGreater,
-    TokenGreaterEqual,
-    TokenLess,
-    TokenLessEqual,
-    TokenIdentifier,
-    TokenString,
-    TokenNumber,
-    TokenAnd,
-    TokenClass,
-    TokenElse,
-    TokenFalse,
-    TokenFor,
-    TokenFun,
-    TokenIf,
-    TokenNil,
-    TokenOr,
-    TokenPrint,
-    TokenReturn,
-    TokenSuper,
-    TokenThis,
-    TokenTrue,
-    TokenVar,
-    TokenWhile,
-    TokenError,
-    TokenEof,
-}
-
-fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
-}
-
-fn is_alpha(c: char) -> bool {
-    (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
-}
-
-impl Scanner {
-    pub fn new(source: &str) -> Scanner {
-        Scanner {
-            line: 1,
-            current: 0,
-            start: 0,
-            source: source.into(),
-        }
-    }
-
-    pub fn scan_token(&mut self) -> Token {
-        if self.is_at_end() {
-            return self.make_token(TokenType::TokenEof);
-        }
-
-        self.skip_whitespace();
-        self.start = self.current;
-
-        if self.is_at_end() {
-            return self.make_token(TokenType::TokenEof);
-        }
-
-        let c = self.advance();
-
-        if is_alpha(c) {
-            return self.identifier();
-        }
-
-        if is_digit(c) {
-            return self.number();
-        }
-
-        match c {
-            '(' => return self.make_token(TokenType::TokenLeftParen),
-            ')' => return self.make_token(TokenType::TokenRightParen),
-            '{' => return self.make_token(TokenType::TokenLeftBrace),
-            '}' => return self.make_token(TokenType::TokenRightBrace),
-            ';' => return self.make_token(TokenType::TokenSemicolon),
-            ',' => return self.make_token(TokenType::TokenComma),
-            '.' => return self.make_token(TokenType::TokenDot),
-            '-' => return self.make_token(TokenType::TokenMinus),
-            '+' => return self.make_token(TokenType::TokenPlus),
-            '/' => return self.make_token(TokenType::TokenSlash),
-            '*' => return self.make_token(TokenType::TokenStar),
-            '!' => {
-                let token_type = if self.match_char('=') {
-                    TokenType::TokenBangEqual
-                } else {
-                    TokenType::TokenBang
-                };
-                return self.make_token(token_type);
-            }
-            '=' => {
-                let token_type = if self.match_char('=') {
-                    TokenType::TokenEqualEqual
-                } else {
-                    TokenType::TokenEqual
-                };
-                return self.make_token(token_type);
-            }
-            '<' => {
-                let token_type = if self.match_char('=') {
-                    TokenType::TokenLessEqual
-                } else {
-                    TokenType::TokenLess
-                };
-                return self.make_token(token_type);
-            }
-            '>' => {
-                let token_type = if self.match_char('=') {
-                    TokenType::TokenGreaterEqual
-                } else {
-                    TokenType::TokenGreater
-                };
-                return self.make_token(token_type);
-            }
-            '"' => {
-                return self.string();
-            }
-
-            _ => {
-                println!("Unexpected character:{}", c);
-                return self.error_token("Unexpected character.");
-            }
-        }
-    }
-
-    fn identifier(&mut self) -> Token {
-        while is_alpha(self.peek()) || is_digit(self.peek()) {
-            self.advance();
-        }
-
-        let identifier_type = self.identifier_type();
-        self.make_token(identifier_type)
-    }
-
-    fn identifier_type(&mut self) -> TokenType {
-        let start_char = self.source.as_bytes()[self.start] as char;
-        let default = TokenType::TokenIdentifier;
-
-        match start_char {
-            'a' => {
-                return self.check_keyword(1, 2, "nd", TokenType::TokenAnd);
-            }
-            'c' => {
-                return self.check_keyword(1, 4, "lass", TokenType::TokenClass);
-            }
-            'e' => {
-                return self.check_keyword(1, 3, "lse", TokenType::TokenElse);
-            }
-            'i' => {
-                return self.check_keyword(1, 1, "f", TokenType::TokenIf);
-            }
-            'n' => {
-                return self.check_keyword(1, 2, "il", TokenType::TokenNil);
-            }
-            'o' => {
-                return self.check_keyword(1, 1, "r", TokenType::TokenOr);
-            }
-            'p' => {
-                return self.check_keyword(1, 4, "rint", TokenType::TokenPrint);
-            }
-            'r' => {
-                return self.check_keyword(1, 5, "eturn", TokenType::TokenReturn);
-            }
-            's' => {
-                return self.check_keyword(1, 4, "uper", TokenType::TokenSuper);
-            }
-            'v' => {
-                return self.check_keyword(1, 2, "ar", TokenType::TokenVar);
-            }
-            'w' => {
-                return self.check_keyword(1, 4, "hile", TokenType::TokenWhile);
-            }
-            'f' => {
-                if self.current - self.start > 1 {
-                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
-                    match next_starting_char {
-                        'a' => {
-                            return self.check_keyword(2, 3, "lse", TokenType::TokenFalse);
-                        }
-                        'o' => {
-                            return self.check_keyword(2, 1, "r", TokenType::TokenFor);
-                        }
-                        'u' => {
-                            return self.check_keyword(2, 1, "n", TokenType::TokenFun);
-                        }
-                        _ => return default,
-                    }
-                }
-                default
-            }
-            't' => {
-                if self.current - self.start > 1 {
-                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
-                    match next_starting_char {
-                        'h' => {
-                            return self.check_keyword(2, 2, "is", TokenType::TokenThis);
-                        }
-                        'r' => {
-                            return self.check_keyword(2, 2, "ue", TokenType::TokenTrue);
-                        }
-                        _ => return default,
-                    }
-                }
-                default
-            }
-            _ => default,
-        }
-    }
-
-    fn check_keyword(
-        &mut self,
-        start: usize,
-        length: usize,
-        rest: &str,
-        token_type: TokenType,
-    ) -> TokenType {
-        if self.current - self.start == start + length {
-            for i in 0..length {
-                let current_substring_char = self.source.as_bytes()[self.start + start + i];
-                let to_compare_to = rest.as_bytes()[i];
-
-                if current_substring_char != to_compare_to {
-                    return TokenType::TokenIdentifier;
-                }
-            }
-            return token_type;
-        }
-
-        TokenType::TokenIdentifier
-    }
-
-    fn number(&mut self) -> Token {
-        while is_digit(self.peek()) {
-            self.advance();
-        }
-
-        if self.peek() == '.' && is_digit(self.peek_next()) {
-            self.advance();
-
-            while is_digit(self.peek()) {
-                self.advance();
-            }
-        }
-
-        self.make_token(TokenType::TokenNumber)
-    }
-
-    fn string(&mut self) -> Token {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line = self.line + 1;
-            }
-            self.advance();
-        }
-
-        if self.is_at_end() {
-            return self.error_token("Unterminated string");
-        }
-
-        self.advance();
-
-        self.make_token(TokenType::TokenString)
-    }
-
-    fn skip_whitespace(&mut self) {
-        loop {
-            if self.is_at_end() {
-                return;
-            }
-            let c = self.peek();
-            match c {
-                ' ' | '\r' | '\t' => {
-                    self.advance();
-                }
-                '\n' => {
-                    self.line = self.line + 1;
-                    self.advance();
-                }
-                '/' => {
-                    if self.peek_next() == '/' {
-                        while !self.is_at_end() && self.peek() != '\n' {
-                            self.advance();
-                        }
-                    } else {
-                        return;
-                    }
-                }
-                _ => {
-                    return;
-                }
-            }
-        }
-    }
-
-    fn peek_next(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-
-        let next_char = self.source.as_bytes()[self.current + 1];
-        next_char as char
-    }
-
-    fn peek(&self) -> char {
-        let current_char = self.source.as_bytes()[self.current];
-        current_char as char
-    }
-
-    fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-
-        if self.get_current_char() != expected {
-            return false;
-        }
-
-        self.current = self.current + 1;
-        return true;
-    }
-
-    fn get_current_char(&self) -> char {
-        let current_char = self.source.as_bytes()[self.current];
-        current_char as char
-    }
-
-    fn advance(&mut self) -> char {
-        let current_char = self.get_current_char();
-        self.current = self.current + 1;
-        current_char
-    }
-
-    fn is_at_end(&self) -> bool {
-        let is_at_end = self.current >= self.source.as_bytes().len();
-        is_at_end
-    }
-
-    fn make_token(&self, token_type: TokenType) -> Token {
-        let string = self.source[self.start..self.current].into();
-
-        Token {
-            token_type: token_type,
-            start: self.start,
-            length: self.current - self.start,
-            line: self.line,
-            content: string,
-        }
-    }
-
-    fn error_token(&self, message: &str) -> Token {
-        Token {
-            token_type: TokenType::TokenError,
-            start: 0,
-            length: message.as_bytes().len(),
-            line: self.line,
-            content: message.into(),
-        }
-    }
-}
+#![allow(
+    clippy::needless_return,
+    clippy::assign_op_pattern,
+    clippy::redundant_field_names,
+    clippy::manual_range_contains,
+    clippy::let_and_return
+)]
+
+pub struct Scanner {
+    line: usize,
+    column: usize,
+    source: String,
+    start: usize,
+    start_line: usize,
+    start_column: usize,
+    current: usize,
+    // One entry per string interpolation being scanned, counting the braces
+    // opened inside its `${...}` expression.
+    interpolations: Vec<usize>,
+}
+
+// Where the scanner is, saved to look ahead a few tokens and rewind.
+pub struct Checkpoint {
+    line: usize,
+    column: usize,
+    current: usize,
+    interpolations: Vec<usize>,
+}
+
+// Location of a token in the source: `start..end` are byte offsets, `line`
+// and `column` (both starting at 1) are where the token begins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start: start,
+            end: end,
+            line: line,
+            column: column,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub token_type: TokenType,
+    pub span: Span,
+    pub content: String,
+    // Value of a string literal once its quotes are stripped and its escape
+    // sequences decoded. `content` keeps the lexeme as written.
+    pub literal: Option<String>,
+}
+
+impl Token {
+    pub const fn new_dummy_token() -> Token {
+        Token {
+            token_type: TokenType::TokenNumber,
+            span: Span::new(0, 0, 0, 0),
+            content: String::new(),
+            literal: None,
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenType {
+    TokenLeftParen,
+    TokenRightParen,
+    TokenLeftBrace,
+    TokenRightBrace,
+    TokenLeftBracket,
+    TokenRightBracket,
+    TokenComma,
+    TokenDot,
+    TokenDotDot,
+    TokenDotDotEqual,
+    TokenMinus,
+    TokenMinusEqual,
+    TokenMinusMinus,
+    TokenPlus,
+    TokenPlusEqual,
+    TokenPlusPlus,
+    TokenSemicolon,
+    TokenColon,
+    TokenQuestion,
+    TokenQuestionQuestion,
+    TokenSlash,
+    TokenSlashEqual,
+    TokenStar,
+    TokenStarEqual,
+    TokenStarStar,
+    TokenPercent,
+    TokenPercentEqual,
+    TokenTilde,
+    TokenTildeSlash,
+    TokenAmpersand,
+    TokenPipe,
+    TokenCaret,
+    TokenBang,
+    TokenBangEqual,
+    TokenEqual,
+    TokenEqualEqual,
+    TokenGreater,
+    TokenGreaterEqual,
+    TokenGreaterGreater,
+    TokenLess,
+    TokenLessEqual,
+    TokenLessLess,
+    TokenIdentifier,
+    TokenString,
+    TokenInterpolation,
+    TokenNumber,
+    TokenAnd,
+    TokenBreak,
+    TokenCase,
+    TokenClass,
+    TokenConst,
+    TokenContinue,
+    TokenDefault,
+    TokenElse,
+    TokenFalse,
+    TokenFor,
+    TokenFun,
+    TokenIf,
+    TokenIn,
+    TokenNil,
+    TokenOr,
+    TokenPrint,
+    TokenReturn,
+    TokenSuper,
+    TokenSwitch,
+    TokenThis,
+    TokenTrue,
+    TokenVar,
+    TokenWhile,
+    TokenError,
+    TokenEof,
+}
+
+fn is_digit(c: char) -> bool {
+    c >= '0' && c <= '9'
+}
+
+fn is_alpha(c: char) -> bool {
+    (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+}
+
+#[cfg(feature = "unicode-identifiers")]
+fn is_identifier_start(c: char) -> bool {
+    is_alpha(c) || (!c.is_ascii() && unicode_xid::UnicodeXID::is_xid_start(c))
+}
+
+#[cfg(not(feature = "unicode-identifiers"))]
+fn is_identifier_start(c: char) -> bool {
+    is_alpha(c)
+}
+
+#[cfg(feature = "unicode-identifiers")]
+fn is_identifier_continue(c: char) -> bool {
+    is_alpha(c) || is_digit(c) || (!c.is_ascii() && unicode_xid::UnicodeXID::is_xid_continue(c))
+}
+
+#[cfg(not(feature = "unicode-identifiers"))]
+fn is_identifier_continue(c: char) -> bool {
+    is_alpha(c) || is_digit(c)
+}
+
+impl Scanner {
+    pub fn new(source: &str) -> Scanner {
+        Scanner {
+            line: 1,
+            column: 1,
+            current: 0,
+            start: 0,
+            start_line: 1,
+            start_column: 1,
+            source: source.into(),
+            interpolations: vec![],
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            line: self.line,
+            column: self.column,
+            current: self.current,
+            interpolations: self.interpolations.clone(),
+        }
+    }
+
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.line = checkpoint.line;
+        self.column = checkpoint.column;
+        self.current = checkpoint.current;
+        self.interpolations = checkpoint.interpolations;
+    }
+
+    pub fn scan_token(&mut self) -> Token {
+        self.skip_whitespace();
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+
+        if self.is_at_end() {
+            return self.make_token(TokenType::TokenEof);
+        }
+
+        let c = self.advance();
+
+        if is_identifier_start(c) {
+            return self.identifier();
+        }
+
+        if is_digit(c) {
+            return self.number();
+        }
+
+        match c {
+            '(' => return self.make_token(TokenType::TokenLeftParen),
+            ')' => return self.make_token(TokenType::TokenRightParen),
+            '[' => return self.make_token(TokenType::TokenLeftBracket),
+            ']' => return self.make_token(TokenType::TokenRightBracket),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth = *depth + 1;
+                }
+                return self.make_token(TokenType::TokenLeftBrace);
+            }
+            '}' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    if *depth == 0 {
+                        self.interpolations.pop();
+                        return self.string();
+                    }
+                    *depth = *depth - 1;
+                }
+                return self.make_token(TokenType::TokenRightBrace);
+            }
+            ';' => return self.make_token(TokenType::TokenSemicolon),
+            ':' => return self.make_token(TokenType::TokenColon),
+            '?' => {
+                let token_type = if self.match_char('?') {
+                    TokenType::TokenQuestionQuestion
+                } else {
+                    TokenType::TokenQuestion
+                };
+                return self.make_token(token_type);
+            }
+            ',' => return self.make_token(TokenType::TokenComma),
+            '.' => {
+                let token_type = if !self.match_char('.') {
+                    TokenType::TokenDot
+                } else if self.match_char('=') {
+                    TokenType::TokenDotDotEqual
+                } else {
+                    TokenType::TokenDotDot
+                };
+                return self.make_token(token_type);
+            }
+            '-' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenMinusEqual
+                } else if self.match_char('-') {
+                    TokenType::TokenMinusMinus
+                } else {
+                    TokenType::TokenMinus
+                };
+                return self.make_token(token_type);
+            }
+            '+' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenPlusEqual
+                } else if self.match_char('+') {
+                    TokenType::TokenPlusPlus
+                } else {
+                    TokenType::TokenPlus
+                };
+                return self.make_token(token_type);
+            }
+            '/' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenSlashEqual
+                } else {
+                    TokenType::TokenSlash
+                };
+                return self.make_token(token_type);
+            }
+            '*' => {
+                let token_type = if self.match_char('*') {
+                    TokenType::TokenStarStar
+                } else if self.match_char('=') {
+                    TokenType::TokenStarEqual
+                } else {
+                    TokenType::TokenStar
+                };
+                return self.make_token(token_type);
+            }
+            '%' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenPercentEqual
+                } else {
+                    TokenType::TokenPercent
+                };
+                return self.make_token(token_type);
+            }
+            // `//` starts a comment, so floor division is spelled `~/`.
+            '~' => {
+                let token_type = if self.match_char('/') {
+                    TokenType::TokenTildeSlash
+                } else {
+                    TokenType::TokenTilde
+                };
+                return self.make_token(token_type);
+            }
+            '&' => return self.make_token(TokenType::TokenAmpersand),
+            '|' => return self.make_token(TokenType::TokenPipe),
+            '^' => return self.make_token(TokenType::TokenCaret),
+            '!' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenBangEqual
+                } else {
+                    TokenType::TokenBang
+                };
+                return self.make_token(token_type);
+            }
+            '=' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenEqualEqual
+                } else {
+                    TokenType::TokenEqual
+                };
+                return self.make_token(token_type);
+            }
+            '<' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenLessEqual
+                } else if self.match_char('<') {
+                    TokenType::TokenLessLess
+                } else {
+                    TokenType::TokenLess
+                };
+                return self.make_token(token_type);
+            }
+            '>' => {
+                let token_type = if self.match_char('=') {
+                    TokenType::TokenGreaterEqual
+                } else if self.match_char('>') {
+                    TokenType::TokenGreaterGreater
+                } else {
+                    TokenType::TokenGreater
+                };
+                return self.make_token(token_type);
+            }
+            '"' => {
+                if self.peek() == '"' && self.peek_next() == '"' {
+                    self.advance();
+                    self.advance();
+                    return self.raw_string();
+                }
+                return self.string();
+            }
+
+            _ if !c.is_ascii() => {
+                let message = format!("Unexpected character '{}' (U+{:04X}).", c, c as u32);
+                return self.error_token(&message);
+            }
+            _ => {
+                return self.error_token("Unexpected character.");
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Token {
+        while is_identifier_continue(self.peek()) {
+            self.advance();
+        }
+
+        let identifier_type = self.identifier_type();
+        self.make_token(identifier_type)
+    }
+
+    fn identifier_type(&mut self) -> TokenType {
+        let start_char = self.source.as_bytes()[self.start] as char;
+        let default = TokenType::TokenIdentifier;
+
+        match start_char {
+            'a' => {
+                return self.check_keyword(1, 2, "nd", TokenType::TokenAnd);
+            }
+            'b' => {
+                return self.check_keyword(1, 4, "reak", TokenType::TokenBreak);
+            }
+            'c' => {
+                if self.current - self.start > 1 {
+                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
+                    match next_starting_char {
+                        'a' => {
+                            return self.check_keyword(2, 2, "se", TokenType::TokenCase);
+                        }
+                        'l' => {
+                            return self.check_keyword(2, 3, "ass", TokenType::TokenClass);
+                        }
+                        'o' => {
+                            if self.current - self.start == 5 {
+                                return self.check_keyword(2, 3, "nst", TokenType::TokenConst);
+                            }
+                            return self.check_keyword(2, 6, "ntinue", TokenType::TokenContinue);
+                        }
+                        _ => return default,
+                    }
+                }
+                default
+            }
+            'd' => {
+                return self.check_keyword(1, 6, "efault", TokenType::TokenDefault);
+            }
+            'e' => {
+                return self.check_keyword(1, 3, "lse", TokenType::TokenElse);
+            }
+            'i' => {
+                if self.current - self.start > 1 {
+                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
+                    match next_starting_char {
+                        'f' => {
+                            return self.check_keyword(2, 0, "", TokenType::TokenIf);
+                        }
+                        'n' => {
+                            return self.check_keyword(2, 0, "", TokenType::TokenIn);
+                        }
+                        _ => return default,
+                    }
+                }
+                default
+            }
+            'n' => {
+                return self.check_keyword(1, 2, "il", TokenType::TokenNil);
+            }
+            'o' => {
+                return self.check_keyword(1, 1, "r", TokenType::TokenOr);
+            }
+            'p' => {
+                return self.check_keyword(1, 4, "rint", TokenType::TokenPrint);
+            }
+            'r' => {
+                return self.check_keyword(1, 5, "eturn", TokenType::TokenReturn);
+            }
+            's' => {
+                if self.current - self.start > 1 {
+                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
+                    match next_starting_char {
+                        'u' => {
+                            return self.check_keyword(2, 3, "per", TokenType::TokenSuper);
+                        }
+                        'w' => {
+                            return self.check_keyword(2, 4, "itch", TokenType::TokenSwitch);
+                        }
+                        _ => return default,
+                    }
+                }
+                default
+            }
+            'v' => {
+                return self.check_keyword(1, 2, "ar", TokenType::TokenVar);
+            }
+            'w' => {
+                return self.check_keyword(1, 4, "hile", TokenType::TokenWhile);
+            }
+            'f' => {
+                if self.current - self.start > 1 {
+                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
+                    match next_starting_char {
+                        'a' => {
+                            return self.check_keyword(2, 3, "lse", TokenType::TokenFalse);
+                        }
+                        'o' => {
+                            return self.check_keyword(2, 1, "r", TokenType::TokenFor);
+                        }
+                        'u' => {
+                            return self.check_keyword(2, 1, "n", TokenType::TokenFun);
+                        }
+                        _ => return default,
+                    }
+                }
+                default
+            }
+            't' => {
+                if self.current - self.start > 1 {
+                    let next_starting_char = self.source.as_bytes()[self.start + 1] as char;
+                    match next_starting_char {
+                        'h' => {
+                            return self.check_keyword(2, 2, "is", TokenType::TokenThis);
+                        }
+                        'r' => {
+                            return self.check_keyword(2, 2, "ue", TokenType::TokenTrue);
+                        }
+                        _ => return default,
+                    }
+                }
+                default
+            }
+            _ => default,
+        }
+    }
+
+    fn check_keyword(
+        &mut self,
+        start: usize,
+        length: usize,
+        rest: &str,
+        token_type: TokenType,
+    ) -> TokenType {
+        if self.current - self.start == start + length {
+            for i in 0..length {
+                let current_substring_char = self.source.as_bytes()[self.start + start + i];
+                let to_compare_to = rest.as_bytes()[i];
+
+                if current_substring_char != to_compare_to {
+                    return TokenType::TokenIdentifier;
+                }
+            }
+            return token_type;
+        }
+
+        TokenType::TokenIdentifier
+    }
+
+    fn number(&mut self) -> Token {
+        while is_digit(self.peek()) {
+            self.advance();
+        }
+
+        if self.peek() == '.' && is_digit(self.peek_next()) {
+            self.advance();
+
+            while is_digit(self.peek()) {
+                self.advance();
+            }
+        }
+
+        self.make_token(TokenType::TokenNumber)
+    }
+
+    // Scans string content up to the closing quote, or up to the `${` that
+    // starts an interpolated expression. In the latter case the segment is
+    // returned as a `TokenInterpolation` and scanning resumes in the string
+    // once the matching `}` is reached.
+    fn string(&mut self) -> Token {
+        let mut value = String::new();
+        let mut escape_error: Option<(String, Span)> = None;
+        let mut interpolation = false;
+
+        while !self.is_at_end() && self.peek() != '"' {
+            if self.peek() == '$' && self.peek_next() == '{' {
+                self.advance();
+                self.advance();
+                interpolation = true;
+                break;
+            }
+
+            if self.peek() != '\\' {
+                value.push(self.advance());
+                continue;
+            }
+
+            let escape_start = Span::new(self.current, self.current, self.line, self.column);
+            self.advance();
+            if self.is_at_end() {
+                break;
+            }
+
+            match self.escape_sequence() {
+                Ok(c) => value.push(c),
+                Err(message) => {
+                    if escape_error.is_none() {
+                        let span = Span {
+                            end: self.current,
+                            ..escape_start
+                        };
+                        escape_error = Some((message, span));
+                    }
+                }
+            }
+        }
+
+        if self.is_at_end() && !interpolation {
+            return self.error_token("Unterminated string.");
+        }
+
+        if interpolation {
+            self.interpolations.push(0);
+        } else {
+            self.advance();
+        }
+
+        if let Some((message, span)) = escape_error {
+            return self.error_token_at(&message, span);
+        }
+
+        let token_type = if interpolation {
+            TokenType::TokenInterpolation
+        } else {
+            TokenType::TokenString
+        };
+        let mut token = self.make_token(token_type);
+        token.literal = Some(value);
+        token
+    }
+
+    // Decodes the escape sequence following a backslash.
+    fn escape_sequence(&mut self) -> Result<char, String> {
+        let c = self.advance();
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            '\\' => Ok('\\'),
+            'u' => self.unicode_escape(),
+            _ => Err(format!("Invalid escape sequence '\\{}'.", c)),
+        }
+    }
+
+    // Decodes the `{XXXX}` part of a `\u{XXXX}` escape: one to six hex digits
+    // naming a Unicode scalar value.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        if !self.match_char('{') {
+            return Err("Expect '{' after '\\u'.".into());
+        }
+
+        let mut digits = String::new();
+        while !self.is_at_end() && self.peek().is_ascii_hexdigit() && digits.len() < 6 {
+            digits.push(self.advance());
+        }
+
+        if !self.match_char('}') {
+            return Err("Expect 1 to 6 hex digits and '}' in '\\u{...}' escape.".into());
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("Invalid Unicode scalar value '\\u{{{}}}'.", digits))
+    }
+
+    // Triple-quoted strings are taken verbatim: no escape sequences, and
+    // they may span several lines. A line break right after the opening
+    // quotes is not part of the string, so the content can start on its own
+    // line.
+    fn raw_string(&mut self) -> Token {
+        if self.peek() == '\r' && self.peek_next() == '\n' {
+            self.advance();
+        }
+        if self.peek() == '\n' {
+            self.advance();
+        }
+        let content_start = self.current;
+
+        loop {
+            if self.is_at_end() {
+                return self.error_token("Unterminated raw string.");
+            }
+            if self.source[self.current..].starts_with("\"\"\"") {
+                break;
+            }
+            self.advance();
+        }
+
+        let value = self.source[content_start..self.current].to_string();
+        self.advance();
+        self.advance();
+        self.advance();
+
+        let mut token = self.make_token(TokenType::TokenString);
+        token.literal = Some(value);
+        token
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            if self.is_at_end() {
+                return;
+            }
+            let c = self.peek();
+            match c {
+                ' ' | '\r' | '\t' | '\n' => {
+                    self.advance();
+                }
+                '/' if self.peek_next() == '/' => {
+                    while !self.is_at_end() && self.peek() != '\n' {
+                        self.advance();
+                    }
+                }
+                _ => {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn peek_next(&self) -> char {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
+    }
+
+    fn peek(&self) -> char {
+        self.get_current_char()
+    }
+
+    fn match_char(&mut self, expected: char) -> bool {
+        if self.is_at_end() {
+            return false;
+        }
+
+        if self.get_current_char() != expected {
+            return false;
+        }
+
+        self.advance();
+        return true;
+    }
+
+    // The source is walked one UTF-8 encoded character at a time, so
+    // `current` always sits on a character boundary.
+    fn get_current_char(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn advance(&mut self) -> char {
+        let current_char = self.get_current_char();
+        self.current = self.current + current_char.len_utf8();
+
+        if current_char == '\n' {
+            self.line = self.line + 1;
+            self.column = 1;
+        } else {
+            self.column = self.column + 1;
+        }
+
+        current_char
+    }
+
+    fn is_at_end(&self) -> bool {
+        let is_at_end = self.current >= self.source.len();
+        is_at_end
+    }
+
+    fn make_token(&self, token_type: TokenType) -> Token {
+        let string = self.source[self.start..self.current].into();
+
+        Token {
+            token_type: token_type,
+            span: self.current_span(),
+            content: string,
+            literal: None,
+        }
+    }
+
+    fn error_token(&self, message: &str) -> Token {
+        self.error_token_at(message, self.current_span())
+    }
+
+    fn error_token_at(&self, message: &str, span: Span) -> Token {
+        Token {
+            token_type: TokenType::TokenError,
+            span: span,
+            content: message.into(),
+            literal: None,
+        }
+    }
+
+    fn current_span(&self) -> Span {
+        Span::new(self.start, self.current, self.start_line, self.start_column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every token up to and including the end of input.
+    fn scan(source: &str) -> Vec<Token> {
+        let mut scanner = Scanner::new(source);
+        let mut tokens = vec![];
+        loop {
+            let token = scanner.scan_token();
+            let done = token.token_type == TokenType::TokenEof;
+            tokens.push(token);
+            if done {
+                return tokens;
+            }
+        }
+    }
+
+    fn spans(source: &str) -> Vec<(TokenType, Span)> {
+        scan(source)
+            .into_iter()
+            .map(|token| (token.token_type, token.span))
+            .collect()
+    }
+
+    #[test]
+    fn spans_on_one_line() {
+        assert_eq!(
+            spans("var ab = 12;"),
+            vec![
+                (TokenType::TokenVar, Span::new(0, 3, 1, 1)),
+                (TokenType::TokenIdentifier, Span::new(4, 6, 1, 5)),
+                (TokenType::TokenEqual, Span::new(7, 8, 1, 8)),
+                (TokenType::TokenNumber, Span::new(9, 11, 1, 10)),
+                (TokenType::TokenSemicolon, Span::new(11, 12, 1, 12)),
+                (TokenType::TokenEof, Span::new(12, 12, 1, 13)),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_and_columns_restart_after_newlines() {
+        assert_eq!(
+            spans("print\n  x; // note\n\ty"),
+            vec![
+                (TokenType::TokenPrint, Span::new(0, 5, 1, 1)),
+                (TokenType::TokenIdentifier, Span::new(8, 9, 2, 3)),
+                (TokenType::TokenSemicolon, Span::new(9, 10, 2, 4)),
+                (TokenType::TokenIdentifier, Span::new(20, 21, 3, 2)),
+                (TokenType::TokenEof, Span::new(21, 21, 3, 3)),
+            ]
+        );
+    }
+
+    // A token keeps the line and column it starts on; the tokens after it
+    // are placed on the line it ends on.
+    #[test]
+    fn multi_line_string() {
+        assert_eq!(
+            spans("x = \"one\ntwo\";"),
+            vec![
+                (TokenType::TokenIdentifier, Span::new(0, 1, 1, 1)),
+                (TokenType::TokenEqual, Span::new(2, 3, 1, 3)),
+                (TokenType::TokenString, Span::new(4, 13, 1, 5)),
+                (TokenType::TokenSemicolon, Span::new(13, 14, 2, 5)),
+                (TokenType::TokenEof, Span::new(14, 14, 2, 6)),
+            ]
+        );
+    }
+
+    // Offsets count bytes, columns count characters.
+    #[test]
+    fn utf8_string() {
+        assert_eq!(
+            spans("print \"héllo→\" + x;"),
+            vec![
+                (TokenType::TokenPrint, Span::new(0, 5, 1, 1)),
+                (TokenType::TokenString, Span::new(6, 17, 1, 7)),
+                (TokenType::TokenPlus, Span::new(18, 19, 1, 16)),
+                (TokenType::TokenIdentifier, Span::new(20, 21, 1, 18)),
+                (TokenType::TokenSemicolon, Span::new(21, 22, 1, 19)),
+                (TokenType::TokenEof, Span::new(22, 22, 1, 20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn utf8_in_comment_and_multi_line_string() {
+        assert_eq!(
+            spans("// ünïcode\n\"ä\nö\" y"),
+            vec![
+                (TokenType::TokenString, Span::new(13, 20, 2, 1)),
+                (TokenType::TokenIdentifier, Span::new(21, 22, 3, 4)),
+                (TokenType::TokenEof, Span::new(22, 22, 3, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn span_matches_content() {
+        let source = "var s = \"π ≈ 3.14\";\nprint s;";
+        for token in scan(source) {
+            assert_eq!(&source[token.span.start..token.span.end], token.content);
+        }
+    }
+
+    #[test]
+    fn rewind_returns_to_checkpoint() {
+        let mut scanner = Scanner::new("for (x in\n  \"${xs}\")");
+        scanner.scan_token();
+        let checkpoint = scanner.checkpoint();
+        let ahead: Vec<TokenType> = (0..5).map(|_| scanner.scan_token().token_type).collect();
+
+        scanner.rewind(checkpoint);
+        let again: Vec<TokenType> = (0..5).map(|_| scanner.scan_token().token_type).collect();
+        assert_eq!(ahead, again);
+        assert_eq!(scanner.scan_token().span, Span::new(17, 19, 2, 8));
+    }
+
+    #[test]
+    fn unexpected_character_after_utf8() {
+        let tokens = scan("\"é\" @");
+        assert_eq!(tokens[1].token_type, TokenType::TokenError);
+        assert_eq!(tokens[1].span, Span::new(5, 6, 1, 5));
+    }
+}
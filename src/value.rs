@@ -1,88 +1,135 @@
-use generational_arena::Index;
-
-use crate::{object::Object, vm::VM};
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum Value {
-    Boolean(bool),
-    Number(f64),
-    Object(Index, Object),
-    Nil,
-}
-
-pub fn values_equal(a: Value, b: Value) -> bool {
-    return a == b;
-}
-
-impl Value {
-    pub fn is_bool(&self) -> bool {
-        match &self {
-            Self::Boolean(_) => true,
-            _ => false,
-        }
-    }
-
-    pub fn is_nil(&self) -> bool {
-        match &self {
-            Self::Nil => true,
-            _ => false,
-        }
-    }
-
-    pub fn is_number(&self) -> bool {
-        match &self {
-            Self::Number(_) => true,
-            _ => false,
-        }
-    }
-
-    pub fn is_object(&self) -> bool {
-        match &self {
-            Self::Object(_, _) => true,
-            _ => false,
-        }
-    }
-
-    pub fn is_string(&self) -> bool {
-        match &self {
-            Self::Object(_, a) => a.is_string(),
-            _ => false,
-        }
-    }
-
-    pub fn as_object(&self) -> Object {
-        match self {
-            Self::Object(_, a) => return a.clone(),
-            _ => panic!("try to cast a non object value"),
-        }
-    }
-
-    pub fn as_bool(&self) -> bool {
-        match &self {
-            Self::Boolean(a) => return *a,
-            _ => panic!("try to cast a non bool value"),
-        }
-    }
-
-    pub fn as_number(&self) -> f64 {
-        match &self {
-            Self::Number(a) => return *a,
-            _ => panic!("try to cast a non number value"),
-        }
-    }
-}
-
-impl Default for Value {
-    fn default() -> Self {
-        Value::Nil
-    }
-}
-
-pub fn print_value(value: Value, vm: &VM) {
-    match value {
-        Value::Boolean(b) => println!("{}", b),
-        Value::Nil => println!("nil"),
-        Value::Number(n) => println!("{}", n),
-        Value::Object(id, o) => vm.print_object(&id, &o),
-    }
-}
+#![allow(
+    clippy::needless_return,
+    clippy::match_like_matches_macro
+)]
+
+use generational_arena::Index;
+
+use crate::{object::Object, vm::VM};
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Value {
+    Boolean(bool),
+    Number(f64),
+    Object(Index, Object),
+    #[default]
+    Nil,
+}
+
+pub fn values_equal(a: Value, b: Value) -> bool {
+    return a == b;
+}
+
+impl Value {
+    pub fn is_bool(&self) -> bool {
+        match &self {
+            Self::Boolean(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        match &self {
+            Self::Nil => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        match &self {
+            Self::Number(_) => true,
+            _ => false,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_object(&self) -> bool {
+        match &self {
+            Self::Object(_, _) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_string(&self) -> bool {
+        match &self {
+            Self::Object(_, a) => a.is_string(),
+            _ => false,
+        }
+    }
+
+    pub fn as_object(&self) -> Object {
+        match self {
+            Self::Object(_, a) => return a.clone(),
+            _ => panic!("try to cast a non object value"),
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        match &self {
+            Self::Boolean(a) => return *a,
+            _ => panic!("try to cast a non bool value"),
+        }
+    }
+
+    pub fn as_number(&self) -> f64 {
+        match &self {
+            Self::Number(a) => return *a,
+            _ => panic!("try to cast a non number value"),
+        }
+    }
+}
+
+// Formats a number the way clox's `printf("%g")` does: six significant
+// digits, trailing zeros dropped, and an exponent when it is below -4 or
+// at least 6. Non-finite values print as `nan`, `inf` and `-inf`.
+pub fn format_number(number: f64) -> String {
+    if number.is_nan() {
+        return "nan".into();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 { "inf".into() } else { "-inf".into() };
+    }
+    if number == 0.0 {
+        return if number.is_sign_negative() { "-0".into() } else { "0".into() };
+    }
+
+    // Rounding to six significant digits can carry into the exponent
+    // (999999.5 becomes 1e+06), so it is taken from the rounded form.
+    let scientific = format!("{:.5e}", number);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+
+    if !(-4..6).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        return format!(
+            "{}e{}{:02}",
+            trim_fraction(mantissa),
+            sign,
+            exponent.abs()
+        );
+    }
+
+    let decimals = (5 - exponent) as usize;
+    trim_fraction(&format!("{:.*}", decimals, number)).into()
+}
+
+fn trim_fraction(digits: &str) -> &str {
+    if !digits.contains('.') {
+        return digits;
+    }
+    digits.trim_end_matches('0').trim_end_matches('.')
+}
+
+pub fn format_value(value: &Value, vm: &VM) -> String {
+    match value {
+        Value::Boolean(b) => b.to_string(),
+        Value::Nil => "nil".into(),
+        Value::Number(n) => format_number(*n),
+        Value::Object(id, o) => vm.format_object(id, o),
+    }
+}
+
+pub fn print_value(value: Value, vm: &mut VM) {
+    let text = format_value(&value, vm);
+    vm.write_output(&text);
+}
@@ -1,434 +1,1328 @@
-use generational_arena::{Arena, Index};
-use std::collections::HashMap;
-
-use crate::{
-    chunk::{map_binary_to_opcode, Chunk, OpCode},
-    compiler::Parser,
-    object::{Object, ObjectString},
-    value::{print_value, values_equal, Value},
-};
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum InterpretResult {
-    InterpretOk,
-    InterpretCompileError,
-    InterpretRuntimeError,
-}
-
-const STACK_MAX: usize = 256;
-const INIT: Value = Value::Nil;
-
-struct VMString {
-    pub strings: Arena<String>,
-    pub string_to_string_index: HashMap<String, Index>,
-    pub string_to_string_obj: HashMap<String, Index>,
-}
-
-impl VMString {
-    pub fn new() -> VMString {
-        VMString {
-            strings: Arena::new(),
-            string_to_string_index: HashMap::new(),
-            string_to_string_obj: HashMap::new(),
-        }
-    }
-}
-
-pub struct VM {
-    chunk: Chunk,
-    ip: usize,
-    stack: [Value; STACK_MAX],
-    stack_top: usize,
-    objects: Arena<Object>,
-    globals: HashMap<String, Value>,
-    strings: VMString,
-}
-
-fn is_falsey(value: Value) -> bool {
-    value.is_nil() || (value.is_bool() && !value.as_bool())
-}
-
-impl VM {
-    pub fn new(chunk: Chunk) -> VM {
-        let array = [INIT; STACK_MAX];
-        VM {
-            chunk: chunk,
-            ip: 0,
-            stack: array,
-            stack_top: 0,
-            objects: Arena::new(),
-            globals: HashMap::new(),
-            strings: VMString::new(),
-        }
-    }
-
-    pub fn reset_stack(&mut self) {
-        self.stack_top = 0;
-    }
-
-    pub fn interpret(&mut self, source: &str) -> InterpretResult {
-        let mut parser = Parser::new(source);
-
-        if !parser.compile(self) {
-            return InterpretResult::InterpretCompileError;
-        }
-
-        self.chunk = parser.get_compiling_chunk();
-        self.stack_top = 0;
-        self.ip = 0;
-        self.run()
-    }
-
-    fn push(&mut self, value: Value) {
-        self.stack[self.stack_top] = value;
-        self.stack_top = self.stack_top + 1;
-    }
-
-    fn pop(&mut self) -> Value {
-        self.stack_top = self.stack_top - 1;
-        self.stack[self.stack_top].clone()
-    }
-
-    fn peek(&self, distance: usize) -> Value {
-        self.stack[self.stack_top - 1 - distance].clone()
-    }
-
-    fn run(&mut self) -> InterpretResult {
-        loop {
-            let instruction = self.read_instruction();
-            match instruction {
-                OpCode::OpReturn => {
-                    //print_value(self.pop());
-                    return InterpretResult::InterpretOk;
-                }
-                OpCode::OpConstant => {
-                    let value = self.read_constant();
-                    self.push(value);
-                }
-                OpCode::OpNot => {
-                    let popped = self.pop();
-                    self.push(Value::Boolean(is_falsey(popped)))
-                }
-                OpCode::OpNegate => {
-                    if !self.peek(0).is_number() {
-                        self.runtime_error("Operand must be a number.");
-                        return InterpretResult::InterpretRuntimeError;
-                    }
-                    let value = self.pop();
-                    self.push(Value::Number(-value.as_number()));
-                }
-                op @ OpCode::OpAdd => {
-                    let result = self.binary_op(op);
-                    if result != InterpretResult::InterpretOk {
-                        return result;
-                    }
-                }
-                op @ OpCode::OpSubtract => {
-                    let result = self.binary_op(op);
-                    if result != InterpretResult::InterpretOk {
-                        return result;
-                    }
-                }
-                op @ OpCode::OpMultiply => {
-                    let result = self.binary_op(op);
-                    if result != InterpretResult::InterpretOk {
-                        return result;
-                    }
-                }
-                op @ OpCode::OpDivide => {
-                    let result = self.binary_op(op);
-                    if result != InterpretResult::InterpretOk {
-                        return result;
-                    }
-                }
-                OpCode::OpNil => {
-                    self.push(Value::Nil);
-                }
-                OpCode::OpTrue => {
-                    self.push(Value::Boolean(true));
-                }
-                OpCode::OpFalse => {
-                    self.push(Value::Boolean(false));
-                }
-                OpCode::OpEqual => {
-                    let b = self.pop();
-                    let a = self.pop();
-
-                    self.push(Value::Boolean(values_equal(a, b)));
-                }
-                op @ OpCode::OpGreater => {
-                    let result = self.binary_op(op);
-                    if result != InterpretResult::InterpretOk {
-                        return result;
-                    }
-                }
-                op @ OpCode::OpLess => {
-                    let result = self.binary_op(op);
-                    if result != InterpretResult::InterpretOk {
-                        return result;
-                    }
-                }
-                OpCode::OpPrint => {
-                    print_value(self.pop(), &self);
-                }
-                OpCode::OpPop => {
-                    self.pop();
-                }
-                OpCode::OpDefineGlobal => {
-                    let name = self.read_string();
-                    let value = self.peek(0);
-                    self.globals.insert(name, value);
-                    self.pop();
-                }
-                OpCode::OpGetGlobal => {
-                    let name = self.read_string();
-                    let maybe_key = self.globals.get(&name);
-                    if maybe_key.is_none() {
-                        let message = format!("Undefined variable '{}'", &name);
-                        self.runtime_error(&message);
-                        return InterpretResult::InterpretRuntimeError;
-                    }
-                    let key = maybe_key.unwrap().clone();
-                    self.push(key);
-                }
-                OpCode::OpSetGlobal => {
-                    let name = self.read_string();
-                    let value = self.peek(0);
-                    if self.globals.contains_key(&name) {
-                        let existing_value = self.globals.get_mut(&name);
-                        *existing_value.unwrap() = value;
-                    } else {
-                        self.globals.insert(name, value);
-                    }
-                }
-                OpCode::OpGetLocal => {
-                    let slot = self.get_next_byte();
-                    let to_push = self.stack[slot as usize].clone();
-                    self.push(to_push);
-                }
-                OpCode::OpSetLocal => {
-                    let slot = self.get_next_byte();
-                    self.stack[slot as usize] = self.peek(0);
-                }
-                OpCode::OpJumpIfFalse => {
-                    let offset = self.read_short();
-                    if is_falsey(self.peek(0)) {
-                        self.ip = self.ip + (offset as usize);
-                    }
-                },
-                OpCode::OpJump => {
-                    let offset = self.read_short();
-                    self.ip = self.ip + offset as usize;
-                }
-                OpCode::OpLoop => {
-                    let offset = self.read_short();
-                    self.ip = self.ip - offset as usize;
-                }
-            }
-        }
-    }
-
-
-    fn read_short(&mut self) -> u16 {
-        self.ip = self.ip + 2;
-        let a = (self.chunk.code[self.ip - 2] as u16) << 8;
-        let b = self.chunk.code[self.ip - 1] as u16;
-        a | b
-    }
-
-    fn read_string(&mut self) -> String {
-        let constant = self.read_constant();
-        let obj = constant.as_object();
-        let name = obj.as_string();
-        self.strings.strings.get(*name.id()).unwrap().clone()
-    }
-
-    fn runtime_error(&mut self, message: &str) {
-        println!("{}", message);
-
-        self.reset_stack();
-    }
-
-    fn concatenate(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-
-        let b_obj = b.as_object();
-        let a_obj = a.as_object();
-
-        let b_str = b_obj.as_string();
-        let a_str = a_obj.as_string();
-
-        let mut c = String::new();
-
-        c.push_str(self.get_string_from_index(&a_str.id()));
-        c.push_str(self.get_string_from_index(&b_str.id()));
-
-        //let id = self.get_or_create_string(&c);
-
-        //let object = Object::ObjString(ObjectString::new(id));
-        let object = self.get_or_create_string_object(&c);
-        self.push(object);
-    }
-
-    fn binary_op(&mut self, opcode: OpCode) -> InterpretResult {
-        if self.peek(0).is_string() && self.peek(1).is_string() {
-            self.concatenate();
-            return InterpretResult::InterpretOk;
-        }
-
-        if !self.peek(0).is_number() || !self.peek(1).is_number() {
-            self.runtime_error("Operands must be numbers.");
-            return InterpretResult::InterpretRuntimeError;
-        }
-
-        let b = self.pop().as_number();
-        let a = self.pop().as_number();
-
-        let result = match opcode {
-            OpCode::OpAdd => Value::Number(a + b),
-            OpCode::OpSubtract => Value::Number(a - b),
-            OpCode::OpMultiply => Value::Number(a * b),
-            OpCode::OpDivide => Value::Number(a / b),
-            OpCode::OpGreater => Value::Boolean(a > b),
-            OpCode::OpLess => Value::Boolean(a < b),
-            _ => {
-                unimplemented!("binary op not implemented");
-            }
-        };
-
-        self.push(result);
-
-        InterpretResult::InterpretOk
-    }
-
-    fn read_instruction(&mut self) -> OpCode {
-        let byte = self.get_next_byte();
-        map_binary_to_opcode(byte)
-    }
-
-    // READ_BYTE
-    fn get_next_byte(&mut self) -> u8 {
-        let byte = self.chunk.code[self.ip];
-        self.ip = self.ip + 1;
-        byte
-    }
-
-    fn read_constant(&mut self) -> Value {
-        let byte = self.get_next_byte();
-        self.chunk.constants[byte as usize].clone()
-    }
-
-    fn get_string_from_index(&self, index: &Index) -> &String {
-        self.strings.strings.get(*index).unwrap()
-    }
-
-    fn get_index_from_string(&self, string: &str) -> Option<&Index> {
-        self.strings.string_to_string_index.get(string)
-    }
-
-    fn create_new_string(&mut self, string: &str) -> Index {
-        if self.strings.string_to_string_index.contains_key(string) {
-            panic!("Avoid duplication of strings");
-        }
-
-        let id = self.strings.strings.insert(string.into());
-        self.strings
-            .string_to_string_index
-            .insert(string.into(), id);
-        id
-    }
-
-    fn get_or_create_string(&mut self, string: &str) -> (Index, bool) {
-        let mut created = false;
-        let id = self.get_index_from_string(string);
-
-        let id = if id.is_some() {
-            id.unwrap().clone()
-        } else {
-            let id = self.create_new_string(string);
-            created = true;
-            id
-        };
-
-        (id, created)
-    }
-
-    fn create_new_obj_with_existing_string(&mut self, string_index: Index) -> Index {
-        let object = Object::ObjString(ObjectString::new(string_index));
-        let object_index = self.objects.insert(object);
-        object_index
-    }
-
-    pub fn get_or_create_string_object(&mut self, string: &str) -> Value {
-        //::Object(id, obj)
-        let (string_id, was_string_created) = self.get_or_create_string(string);
-
-        let obj_index = if was_string_created {
-            self.create_new_obj_with_existing_string(string_id)
-        } else {
-            let id = self.strings.string_to_string_obj.get(string);
-            id.unwrap().clone()
-        };
-
-        if was_string_created {
-            self.strings
-                .string_to_string_obj
-                .insert(string.into(), obj_index.clone());
-        }
-
-        let obj_string = ObjectString::new(string_id.clone());
-        let obj = Object::ObjString(obj_string);
-        Value::Object(obj_index, obj)
-    }
-
-    pub fn remove_string(&mut self, string: &str) {
-        let id = self
-            .strings
-            .string_to_string_index
-            .get(string.into())
-            .unwrap();
-        self.strings.strings.remove(*id);
-        self.strings.string_to_string_obj.remove_entry(string);
-
-        let _ = self
-            .strings
-            .string_to_string_index
-            .remove_entry(string.into());
-    }
-
-    pub fn print_object(&self, _: &Index, o: &Object) {
-        match o {
-            Object::ObjString(a) => println!("{}", self.get_string_from_index(a.id())),
-        }
-    }
-
-    pub fn dump_stats(&mut self) {
-        println!("================================================");
-        println!("VM contains {} objects", self.objects.len());
-        for (_, object) in self.objects.iter() {
-            print!("object is {:?}. ", object);
-            match &object {
-                Object::ObjString(str_obj) => {
-                    let id = str_obj.id();
-                    let str = self.strings.strings.get(*id).unwrap();
-                    println!("In particular, object is a string: {}", str);
-                }
-            }
-        }
-        println!("================================================");
-        println!("VM contains {} strings", self.strings.strings.len());
-        for (_, string) in self.strings.strings.iter() {
-            println!("VM String: {}", string);
-        }
-
-        println!("================================================");
-        println!("VM contains {} globals", self.globals.len());
-        for (key, value) in self.globals.iter() {
-            println!("object {:?} has key {:?}", key, value);
-        }
-        println!("================================================");
-
-        self.chunk.disassemble_chunk();
-    }
-}
+#![allow(
+    clippy::assign_op_pattern,
+    clippy::redundant_field_names
+)]
+
+use generational_arena::{Arena, Index};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use crate::{
+    chunk::{map_binary_to_opcode, Chunk, OpCode},
+    compiler::Parser,
+    diagnostic::render_excerpt,
+    map::Map,
+    module::Module,
+    natives::{define_natives, Arity, Native, NativeFn, Receiver},
+    object::{
+        Object, ObjectList, ObjectMap, ObjectModule, ObjectNative, ObjectRange, ObjectString,
+    },
+    random::Rng,
+    range::Range,
+    value::{format_number, format_value, print_value, values_equal, Value},
+};
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpretResult {
+    InterpretOk,
+    InterpretCompileError,
+    InterpretRuntimeError,
+}
+
+const STACK_MAX: usize = 256;
+const INIT: Value = Value::Nil;
+
+struct VMString {
+    pub strings: Arena<String>,
+    pub string_to_string_index: HashMap<String, Index>,
+    pub string_to_string_obj: HashMap<String, Index>,
+}
+
+impl VMString {
+    pub fn new() -> VMString {
+        VMString {
+            strings: Arena::new(),
+            string_to_string_index: HashMap::new(),
+            string_to_string_obj: HashMap::new(),
+        }
+    }
+}
+
+pub struct VM {
+    chunk: Chunk,
+    ip: usize,
+    stack: [Value; STACK_MAX],
+    stack_top: usize,
+    stack_overflow: bool,
+    objects: Arena<Object>,
+    globals: HashMap<String, Global>,
+    strings: VMString,
+    lists: Arena<Vec<Value>>,
+    maps: Arena<Map>,
+    ranges: Arena<Range>,
+    modules: Arena<Module>,
+    natives: Vec<Native>,
+    // Slots in `natives` of the methods and properties of each receiver
+    // type, by name.
+    methods: HashMap<(Receiver, String), usize>,
+    properties: HashMap<(Receiver, String), usize>,
+    source: String,
+    output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    options: Options,
+    // Each VM draws its own sequence of random numbers.
+    rng: Rng,
+    // Why the last I/O native failed, for `io_error()`.
+    io_error: Option<String>,
+}
+
+// Settings chosen on the command line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    // Warn about assignments to globals the script never declares.
+    pub warn_undeclared_globals: bool,
+    // Make the file natives fail, for running untrusted scripts.
+    pub disable_filesystem: bool,
+}
+
+struct Global {
+    value: Value,
+    // Declared with `const`: it can be neither assigned nor redefined.
+    constant: bool,
+}
+
+fn is_falsey(value: Value) -> bool {
+    value.is_nil() || (value.is_bool() && !value.as_bool())
+}
+
+// Resolves a possibly negative index into a sequence of `length` elements,
+// `kind` naming the sequence in error messages.
+pub fn resolve_index(index: &Value, length: usize, kind: &str) -> Result<usize, String> {
+    let number = match index {
+        Value::Number(n) if n.fract() == 0.0 && n.is_finite() => *n,
+        _ => return Err(format!("{} index must be an integer.", kind)),
+    };
+
+    let resolved = if number < 0.0 { number + length as f64 } else { number };
+    if resolved < 0.0 || resolved >= length as f64 {
+        return Err(format!(
+            "{} index {} out of bounds for length {}.",
+            kind,
+            format_number(number),
+            length
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+fn as_integer(number: f64) -> Result<i64, &'static str> {
+    if number.fract() != 0.0 || !number.is_finite() {
+        return Err("Operands must be integers.");
+    }
+    // i64::MAX is not representable as an f64; 2^63 is the first value out
+    // of range.
+    if number < i64::MIN as f64 || number >= 9223372036854775808.0 {
+        return Err("Integer operand out of range.");
+    }
+    Ok(number as i64)
+}
+
+impl VM {
+    pub fn new(chunk: Chunk) -> VM {
+        VM::with_output(chunk, Box::new(io::stdout()), Box::new(io::stderr()))
+    }
+
+    // Program output (`print`) goes to `output`, compile and runtime errors
+    // go to `error_output`. Pass `io::sink()` to discard either of them.
+    pub fn with_output(
+        chunk: Chunk,
+        output: Box<dyn Write>,
+        error_output: Box<dyn Write>,
+    ) -> VM {
+        let array = [INIT; STACK_MAX];
+        let mut vm = VM {
+            chunk: chunk,
+            ip: 0,
+            stack: array,
+            stack_top: 0,
+            stack_overflow: false,
+            objects: Arena::new(),
+            globals: HashMap::new(),
+            strings: VMString::new(),
+            lists: Arena::new(),
+            maps: Arena::new(),
+            ranges: Arena::new(),
+            modules: Arena::new(),
+            natives: vec![],
+            methods: HashMap::new(),
+            properties: HashMap::new(),
+            source: String::new(),
+            output: output,
+            error_output: error_output,
+            options: Options::default(),
+            rng: Rng::from_time(),
+            io_error: None,
+        };
+        define_natives(&mut vm);
+        vm
+    }
+
+    pub fn options(&self) -> Options {
+        self.options
+    }
+
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+    }
+
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn io_error(&self) -> Option<&str> {
+        self.io_error.as_deref()
+    }
+
+    pub fn set_io_error(&mut self, error: Option<String>) {
+        self.io_error = error;
+    }
+
+    pub fn write_output(&mut self, text: &str) {
+        let _ = writeln!(self.output, "{}", text);
+    }
+
+    pub fn write_error(&mut self, text: &str) {
+        let _ = writeln!(self.error_output, "{}", text);
+    }
+
+    pub fn reset_stack(&mut self) {
+        self.stack_top = 0;
+        self.stack_overflow = false;
+    }
+
+    pub fn interpret(&mut self, source: &str) -> InterpretResult {
+        let mut parser = Parser::new(source);
+
+        let diagnostics = parser.compile(self);
+        for diagnostic in &diagnostics {
+            self.write_error(&diagnostic.render(source));
+        }
+
+        if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+            return InterpretResult::InterpretCompileError;
+        }
+
+        self.chunk = parser.get_compiling_chunk();
+        self.source = source.into();
+        self.reset_stack();
+        self.ip = 0;
+        self.run()
+    }
+
+    // Pushing onto a full stack drops the value and flags the overflow,
+    // which `run` reports once the instruction is done.
+    fn push(&mut self, value: Value) {
+        if self.stack_top == STACK_MAX {
+            self.stack_overflow = true;
+            return;
+        }
+        self.stack[self.stack_top] = value;
+        self.stack_top = self.stack_top + 1;
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack_top = self.stack_top - 1;
+        self.stack[self.stack_top].clone()
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack_top - 1 - distance].clone()
+    }
+
+    fn run(&mut self) -> InterpretResult {
+        loop {
+            let instruction = self.read_instruction();
+            match instruction {
+                OpCode::OpReturn => {
+                    //print_value(self.pop());
+                    return InterpretResult::InterpretOk;
+                }
+                OpCode::OpConstant => {
+                    let value = self.read_constant();
+                    self.push(value);
+                }
+                OpCode::OpNot => {
+                    let popped = self.pop();
+                    self.push(Value::Boolean(is_falsey(popped)))
+                }
+                OpCode::OpNegate => {
+                    if !self.peek(0).is_number() {
+                        self.runtime_error("Operand must be a number.");
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                    let value = self.pop();
+                    self.push(Value::Number(-value.as_number()));
+                }
+                op @ OpCode::OpAdd => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpSubtract => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpMultiply => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpDivide => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpModulo => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpPower => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpFloorDivide => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpBitAnd => {
+                    let result = self.bitwise_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpBitOr => {
+                    let result = self.bitwise_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpBitXor => {
+                    let result = self.bitwise_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpShiftLeft => {
+                    let result = self.bitwise_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpShiftRight => {
+                    let result = self.bitwise_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                OpCode::OpBitNot => {
+                    if !self.peek(0).is_number() {
+                        self.runtime_error("Operand must be a number.");
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                    match as_integer(self.peek(0).as_number()) {
+                        Ok(value) => {
+                            self.pop();
+                            self.push(Value::Number(!value as f64));
+                        }
+                        Err(_) => {
+                            self.runtime_error("Operand must be an integer.");
+                            return InterpretResult::InterpretRuntimeError;
+                        }
+                    }
+                }
+                OpCode::OpNil => {
+                    self.push(Value::Nil);
+                }
+                OpCode::OpTrue => {
+                    self.push(Value::Boolean(true));
+                }
+                OpCode::OpFalse => {
+                    self.push(Value::Boolean(false));
+                }
+                OpCode::OpEqual => {
+                    let b = self.pop();
+                    let a = self.pop();
+
+                    self.push(Value::Boolean(values_equal(a, b)));
+                }
+                OpCode::OpNotEqual => {
+                    let b = self.pop();
+                    let a = self.pop();
+
+                    self.push(Value::Boolean(!values_equal(a, b)));
+                }
+                op @ OpCode::OpGreater => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpLess => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpGreaterEqual => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                op @ OpCode::OpLessEqual => {
+                    let result = self.binary_op(op);
+                    if result != InterpretResult::InterpretOk {
+                        return result;
+                    }
+                }
+                OpCode::OpPrint => {
+                    print_value(self.pop(), self);
+                }
+                OpCode::OpPop => {
+                    self.pop();
+                }
+                op @ (OpCode::OpDefineGlobal | OpCode::OpDefineConstGlobal) => {
+                    let name = self.read_string();
+                    if self.globals.get(&name).is_some_and(|global| global.constant) {
+                        let message = format!("Can't redefine constant '{}'.", &name);
+                        self.runtime_error(&message);
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                    let global = Global {
+                        value: self.peek(0),
+                        constant: matches!(op, OpCode::OpDefineConstGlobal),
+                    };
+                    self.globals.insert(name, global);
+                    self.pop();
+                }
+                OpCode::OpGetGlobal => {
+                    let name = self.read_string();
+                    let maybe_key = self.globals.get(&name);
+                    if maybe_key.is_none() {
+                        let message = format!("Undefined variable '{}'.", &name);
+                        self.runtime_error(&message);
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                    let key = maybe_key.unwrap().value.clone();
+                    self.push(key);
+                }
+                OpCode::OpSetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0);
+                    let error = match self.globals.get_mut(&name) {
+                        None => Some(format!("Undefined variable '{}'.", &name)),
+                        Some(global) if global.constant => {
+                            Some(format!("Can't assign to constant '{}'.", &name))
+                        }
+                        Some(global) => {
+                            global.value = value;
+                            None
+                        }
+                    };
+                    if let Some(message) = error {
+                        self.runtime_error(&message);
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                }
+                OpCode::OpGetLocal => {
+                    let slot = self.get_next_byte();
+                    let to_push = self.stack[slot as usize].clone();
+                    self.push(to_push);
+                }
+                OpCode::OpSetLocal => {
+                    let slot = self.get_next_byte();
+                    self.stack[slot as usize] = self.peek(0);
+                }
+                OpCode::OpJumpIfFalse => {
+                    let offset = self.read_short();
+                    if is_falsey(self.peek(0)) {
+                        self.ip = self.ip + (offset as usize);
+                    }
+                },
+                OpCode::OpJumpIfNotNil => {
+                    let offset = self.read_short();
+                    if !self.peek(0).is_nil() {
+                        self.ip = self.ip + (offset as usize);
+                    }
+                }
+                OpCode::OpJump => {
+                    let offset = self.read_short();
+                    self.ip = self.ip + offset as usize;
+                }
+                OpCode::OpLoop => {
+                    let offset = self.read_short();
+                    self.ip = self.ip - offset as usize;
+                }
+                OpCode::OpBuildString => {
+                    let part_count = self.get_next_byte() as usize;
+                    let mut string = String::new();
+                    for distance in (0..part_count).rev() {
+                        string.push_str(&format_value(&self.peek(distance), self));
+                    }
+                    self.stack_top = self.stack_top - part_count;
+
+                    let object = self.get_or_create_string_object(&string);
+                    self.push(object);
+                }
+                OpCode::OpCall => {
+                    let arg_count = self.get_next_byte() as usize;
+                    if let Err(message) = self.call_value(self.peek(arg_count), arg_count) {
+                        self.runtime_error(&message);
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                }
+                OpCode::OpBuildList => {
+                    let element_count = self.get_next_byte() as usize;
+                    let first = self.stack_top - element_count;
+                    let elements = self.stack[first..self.stack_top].to_vec();
+                    self.stack_top = first;
+
+                    let list = self.new_list(elements);
+                    self.push(list);
+                }
+                OpCode::OpIterNext => {
+                    let slot = self.get_next_byte() as usize;
+                    let offset = self.read_short();
+                    match self.iterate(slot) {
+                        Ok(Some(element)) => self.push(element),
+                        Ok(None) => self.ip = self.ip + (offset as usize),
+                        Err(message) => {
+                            self.runtime_error(&message);
+                            return InterpretResult::InterpretRuntimeError;
+                        }
+                    }
+                }
+                op @ (OpCode::OpRange | OpCode::OpRangeInclusive) => {
+                    let (Value::Number(start), Value::Number(end)) = (self.peek(1), self.peek(0))
+                    else {
+                        self.runtime_error("Range bounds must be numbers.");
+                        return InterpretResult::InterpretRuntimeError;
+                    };
+                    self.stack_top = self.stack_top - 2;
+
+                    let inclusive = matches!(op, OpCode::OpRangeInclusive);
+                    let range = self.new_range(Range::new(start, end, inclusive));
+                    self.push(range);
+                }
+                OpCode::OpGetProperty => {
+                    let name = self.read_string();
+                    let receiver = self.pop();
+                    match self.get_property(receiver, &name) {
+                        Ok(value) => self.push(value),
+                        Err(message) => {
+                            self.runtime_error(&message);
+                            return InterpretResult::InterpretRuntimeError;
+                        }
+                    }
+                }
+                OpCode::OpInvoke => {
+                    let name = self.read_string();
+                    let arg_count = self.get_next_byte() as usize;
+                    if let Err(message) = self.invoke(&name, arg_count) {
+                        self.runtime_error(&message);
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                }
+                OpCode::OpIn => {
+                    let container = self.pop();
+                    let element = self.pop();
+                    match self.contains(&container, &element) {
+                        Ok(found) => self.push(Value::Boolean(found)),
+                        Err(message) => {
+                            self.runtime_error(&message);
+                            return InterpretResult::InterpretRuntimeError;
+                        }
+                    }
+                }
+                OpCode::OpBuildMap => {
+                    let entry_count = self.get_next_byte() as usize;
+                    let first = self.stack_top - 2 * entry_count;
+                    let mut map = Map::new();
+                    for slot in (first..self.stack_top).step_by(2) {
+                        let key = self.stack[slot].clone();
+                        let value = self.stack[slot + 1].clone();
+                        if let Err(message) = map.insert(key, value) {
+                            self.runtime_error(&message);
+                            return InterpretResult::InterpretRuntimeError;
+                        }
+                    }
+                    self.stack_top = first;
+
+                    let map = self.new_map(map);
+                    self.push(map);
+                }
+                OpCode::OpIndexGet => {
+                    let index = self.pop();
+                    let target = self.pop();
+                    match self.index_get(&target, &index) {
+                        Ok(value) => self.push(value),
+                        Err(message) => {
+                            self.runtime_error(&message);
+                            return InterpretResult::InterpretRuntimeError;
+                        }
+                    }
+                }
+                OpCode::OpIndexSet => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let target = self.pop();
+                    if let Err(message) = self.index_set(&target, &index, value.clone()) {
+                        self.runtime_error(&message);
+                        return InterpretResult::InterpretRuntimeError;
+                    }
+                    self.push(value);
+                }
+                OpCode::OpDupTwo => {
+                    self.push(self.peek(1));
+                    self.push(self.peek(1));
+                }
+                // Moves the top value under the two below it.
+                OpCode::OpRotate => {
+                    let top = self.stack_top - 1;
+                    self.stack[top - 2..=top].rotate_right(1);
+                }
+                // Jumps to the case of the subject on top of the stack, which
+                // stays there. Entries are indexed by the subject minus the
+                // smallest case value.
+                OpCode::OpSwitchNumber => {
+                    let min = self.read_constant().as_number();
+                    let count = self.get_next_byte() as usize;
+                    let table = self.ip;
+                    self.ip = self.ip + 2 * count + 2;
+
+                    let mut entry = table + 2 * count;
+                    if let Value::Number(n) = self.peek(0) {
+                        let i = n - min;
+                        if i.fract() == 0.0 && i >= 0.0 && i < count as f64 {
+                            entry = table + 2 * i as usize;
+                        }
+                    }
+                    self.ip = self.ip + self.read_jump_entry(entry);
+                }
+                OpCode::OpSwitchString => {
+                    let count = self.get_next_byte() as usize;
+                    let table = self.ip;
+                    self.ip = self.ip + 3 * count + 2;
+
+                    let subject = self.peek(0);
+                    let mut entry = table + 3 * count;
+                    for i in 0..count {
+                        let constant = self.chunk.code[table + 3 * i] as usize;
+                        if self.chunk.constants[constant] == subject {
+                            entry = table + 3 * i + 1;
+                            break;
+                        }
+                    }
+                    self.ip = self.ip + self.read_jump_entry(entry);
+                }
+            }
+
+            if self.stack_overflow {
+                self.runtime_error("Stack overflow.");
+                return InterpretResult::InterpretRuntimeError;
+            }
+        }
+    }
+
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), String> {
+        let native = match callee {
+            Value::Object(_, Object::ObjNative(native)) => self.natives[native.id()],
+            _ => return Err("Can only call functions and classes.".into()),
+        };
+        native.arity.check(arg_count)?;
+
+        let args = self.stack[self.stack_top - arg_count..self.stack_top].to_vec();
+        let result = (native.function)(self, &args)?;
+        self.stack_top = self.stack_top - arg_count - 1;
+        self.push(result);
+        Ok(())
+    }
+
+    fn get_property(&mut self, receiver: Value, name: &str) -> Result<Value, String> {
+        if let Value::Object(_, Object::ObjModule(module)) = &receiver {
+            return match self.modules.get(*module.id()).unwrap().get(name) {
+                Some(member) => Ok(member.clone()),
+                None => Err(format!("Undefined property '{}'.", name)),
+            };
+        }
+
+        let property = Receiver::of(&receiver)
+            .and_then(|kind| self.properties.get(&(kind, name.to_string())))
+            .map(|&slot| self.natives[slot]);
+
+        match property {
+            Some(property) => (property.function)(self, &[receiver]),
+            None => Err(format!("Undefined property '{}'.", name)),
+        }
+    }
+
+    // Calls the method `name` on the receiver below the arguments. Without
+    // such a method, the property of that name is called instead.
+    fn invoke(&mut self, name: &str, arg_count: usize) -> Result<(), String> {
+        let receiver = self.peek(arg_count);
+        let method = Receiver::of(&receiver)
+            .and_then(|kind| self.methods.get(&(kind, name.to_string())))
+            .map(|&slot| self.natives[slot]);
+
+        let Some(method) = method else {
+            let callee = self.get_property(receiver, name)?;
+            self.stack[self.stack_top - arg_count - 1] = callee.clone();
+            return self.call_value(callee, arg_count);
+        };
+        method.arity.check(arg_count)?;
+
+        let args = self.stack[self.stack_top - arg_count - 1..self.stack_top].to_vec();
+        let result = (method.function)(self, &args)?;
+        self.stack_top = self.stack_top - arg_count - 1;
+        self.push(result);
+        Ok(())
+    }
+
+    // Steps a for-in loop whose iterable is in `slot` and whose position is
+    // in the slot after it: an element index for lists and maps, a byte
+    // offset for strings. Returns the next element, or None when done.
+    fn iterate(&mut self, slot: usize) -> Result<Option<Value>, String> {
+        let position = self.stack[slot + 1].as_number() as usize;
+        let (element, step) = match &self.stack[slot] {
+            Value::Object(_, Object::ObjList(list)) => match self.list(list).get(position) {
+                Some(element) => (element.clone(), 1),
+                None => return Ok(None),
+            },
+            Value::Object(_, Object::ObjRange(range)) => match self.range(range).get(position) {
+                Some(number) => (Value::Number(number), 1),
+                None => return Ok(None),
+            },
+            // Maps iterate over their keys.
+            Value::Object(_, Object::ObjMap(map)) => match self.map(map).entries().get(position) {
+                Some((key, _)) => (key.clone(), 1),
+                None => return Ok(None),
+            },
+            Value::Object(_, Object::ObjString(string)) => {
+                let text = self.get_string_from_index(string.id());
+                match text[position..].chars().next() {
+                    Some(c) => (self.get_or_create_string_object(&c.to_string()), c.len_utf8()),
+                    None => return Ok(None),
+                }
+            }
+            _ => return Err("Can only iterate over lists, maps, strings and ranges.".into()),
+        };
+
+        self.stack[slot + 1] = Value::Number((position + step) as f64);
+        Ok(Some(element))
+    }
+
+    // `element in container`: list elements, map keys, substrings and the
+    // numbers a range steps through.
+    fn contains(&self, container: &Value, element: &Value) -> Result<bool, String> {
+        match container {
+            Value::Object(_, Object::ObjList(list)) => Ok(self
+                .list(list)
+                .iter()
+                .any(|candidate| values_equal(candidate.clone(), element.clone()))),
+            Value::Object(_, Object::ObjMap(map)) => self.map(map).contains(element),
+            Value::Object(_, Object::ObjRange(range)) => match element {
+                Value::Number(number) => Ok(self.range(range).contains(*number)),
+                _ => Ok(false),
+            },
+            Value::Object(_, Object::ObjString(string)) => match element {
+                Value::Object(_, Object::ObjString(substring)) => {
+                    let text = self.get_string_from_index(string.id());
+                    Ok(text.contains(self.get_string_from_index(substring.id()).as_str()))
+                }
+                _ => Err("Only strings can be searched for in a string.".into()),
+            },
+            _ => Err("Right operand of 'in' must be a list, map, string or range.".into()),
+        }
+    }
+
+    fn index_get(&mut self, target: &Value, index: &Value) -> Result<Value, String> {
+        match (target, index) {
+            (Value::Object(_, Object::ObjList(list)), Value::Object(_, Object::ObjRange(range))) => {
+                let elements = self.list(list);
+                let indices = self.range(range).slice_indices(elements.len())?;
+                let sliced = indices.iter().map(|&i| elements[i].clone()).collect();
+                Ok(self.new_list(sliced))
+            }
+            (Value::Object(_, Object::ObjString(string)), Value::Object(_, Object::ObjRange(range))) => {
+                let chars: Vec<char> = self.get_string_from_index(string.id()).chars().collect();
+                let indices = self.range(range).slice_indices(chars.len())?;
+                let sliced: String = indices.iter().map(|&i| chars[i]).collect();
+                Ok(self.get_or_create_string_object(&sliced))
+            }
+            (Value::Object(_, Object::ObjString(string)), _) => {
+                let text = self.get_string_from_index(string.id());
+                let index = resolve_index(index, text.chars().count(), "String")?;
+                let c = text.chars().nth(index).unwrap();
+                Ok(self.get_or_create_string_object(&c.to_string()))
+            }
+            _ => self.element_get(target, index),
+        }
+    }
+
+    fn element_get(&self, target: &Value, index: &Value) -> Result<Value, String> {
+        match target {
+            Value::Object(_, Object::ObjList(list)) => {
+                let elements = self.list(list);
+                let index = resolve_index(index, elements.len(), "List")?;
+                Ok(elements[index].clone())
+            }
+            // A missing key reads as nil, so `map[key] ?? default` works.
+            Value::Object(_, Object::ObjMap(map)) => {
+                Ok(self.map(map).get(index)?.cloned().unwrap_or_default())
+            }
+            _ => Err("Only lists, maps and strings can be indexed.".into()),
+        }
+    }
+
+    fn index_set(&mut self, target: &Value, index: &Value, value: Value) -> Result<(), String> {
+        match target {
+            Value::Object(_, Object::ObjList(list)) => {
+                let elements = self.list_mut(list);
+                let index = resolve_index(index, elements.len(), "List")?;
+                elements[index] = value;
+                Ok(())
+            }
+            Value::Object(_, Object::ObjMap(map)) => self.map_mut(map).insert(index.clone(), value),
+            Value::Object(_, Object::ObjString(_)) => Err("Strings are immutable.".into()),
+            _ => Err("Only lists and maps support index assignment.".into()),
+        }
+    }
+
+    fn read_short(&mut self) -> u16 {
+        self.ip = self.ip + 2;
+        let a = (self.chunk.code[self.ip - 2] as u16) << 8;
+        let b = self.chunk.code[self.ip - 1] as u16;
+        a | b
+    }
+
+    fn read_jump_entry(&self, entry: usize) -> usize {
+        let a = (self.chunk.code[entry] as usize) << 8;
+        let b = self.chunk.code[entry + 1] as usize;
+        a | b
+    }
+
+    fn read_string(&mut self) -> String {
+        let constant = self.read_constant();
+        let obj = constant.as_object();
+        let name = obj.as_string();
+        self.strings.strings.get(*name.id()).unwrap().clone()
+    }
+
+    fn runtime_error(&mut self, message: &str) {
+        self.write_error(message);
+
+        let span = self.chunk.spans[self.ip - 1];
+        self.write_error(&format!("[line {}] in script", span.line));
+        let excerpt = render_excerpt(&self.source, span);
+        self.write_error(&excerpt);
+
+        self.reset_stack();
+    }
+
+    fn concatenate(&mut self) {
+        let b = self.pop();
+        let a = self.pop();
+
+        let b_obj = b.as_object();
+        let a_obj = a.as_object();
+
+        let b_str = b_obj.as_string();
+        let a_str = a_obj.as_string();
+
+        let mut c = String::new();
+
+        c.push_str(self.get_string_from_index(a_str.id()));
+        c.push_str(self.get_string_from_index(b_str.id()));
+
+        //let id = self.get_or_create_string(&c);
+
+        //let object = Object::ObjString(ObjectString::new(id));
+        let object = self.get_or_create_string_object(&c);
+        self.push(object);
+    }
+
+    fn binary_op(&mut self, opcode: OpCode) -> InterpretResult {
+        let is_add = matches!(opcode, OpCode::OpAdd);
+        if is_add && self.peek(0).is_string() && self.peek(1).is_string() {
+            self.concatenate();
+            return InterpretResult::InterpretOk;
+        }
+
+        if !self.peek(0).is_number() || !self.peek(1).is_number() {
+            self.runtime_error("Operands must be numbers.");
+            return InterpretResult::InterpretRuntimeError;
+        }
+
+        let b = self.pop().as_number();
+        let a = self.pop().as_number();
+
+        let result = match opcode {
+            OpCode::OpAdd => Value::Number(a + b),
+            OpCode::OpSubtract => Value::Number(a - b),
+            OpCode::OpMultiply => Value::Number(a * b),
+            OpCode::OpDivide => Value::Number(a / b),
+            // Floored, so that `a == (a ~/ b) * b + a % b` holds.
+            OpCode::OpModulo => Value::Number(a - b * (a / b).floor()),
+            OpCode::OpPower => Value::Number(a.powf(b)),
+            OpCode::OpFloorDivide => Value::Number((a / b).floor()),
+            OpCode::OpGreater => Value::Boolean(a > b),
+            OpCode::OpLess => Value::Boolean(a < b),
+            OpCode::OpGreaterEqual => Value::Boolean(a >= b),
+            OpCode::OpLessEqual => Value::Boolean(a <= b),
+            _ => {
+                unimplemented!("binary op not implemented");
+            }
+        };
+
+        self.push(result);
+
+        InterpretResult::InterpretOk
+    }
+
+    // Bitwise operators work on numbers holding integral values that fit in
+    // a 64-bit signed integer.
+    fn bitwise_op(&mut self, opcode: OpCode) -> InterpretResult {
+        if !self.peek(0).is_number() || !self.peek(1).is_number() {
+            self.runtime_error("Operands must be numbers.");
+            return InterpretResult::InterpretRuntimeError;
+        }
+
+        let b = self.peek(0).as_number();
+        let a = self.peek(1).as_number();
+        let (a, b) = match (as_integer(a), as_integer(b)) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(message), _) | (_, Err(message)) => {
+                self.runtime_error(message);
+                return InterpretResult::InterpretRuntimeError;
+            }
+        };
+
+        let is_shift = matches!(opcode, OpCode::OpShiftLeft | OpCode::OpShiftRight);
+        if is_shift && !(0..64).contains(&b) {
+            self.runtime_error("Shift amount must be between 0 and 63.");
+            return InterpretResult::InterpretRuntimeError;
+        }
+
+        let result = match opcode {
+            OpCode::OpBitAnd => a & b,
+            OpCode::OpBitOr => a | b,
+            OpCode::OpBitXor => a ^ b,
+            OpCode::OpShiftLeft => a.wrapping_shl(b as u32),
+            OpCode::OpShiftRight => a >> b,
+            _ => {
+                unreachable!();
+            }
+        };
+
+        self.pop();
+        self.pop();
+        self.push(Value::Number(result as f64));
+
+        InterpretResult::InterpretOk
+    }
+
+    fn read_instruction(&mut self) -> OpCode {
+        let byte = self.get_next_byte();
+        map_binary_to_opcode(byte)
+    }
+
+    // READ_BYTE
+    fn get_next_byte(&mut self) -> u8 {
+        let byte = self.chunk.code[self.ip];
+        self.ip = self.ip + 1;
+        byte
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let byte = self.get_next_byte();
+        self.chunk.constants[byte as usize].clone()
+    }
+
+    fn get_string_from_index(&self, index: &Index) -> &String {
+        self.strings.strings.get(*index).unwrap()
+    }
+
+    fn get_index_from_string(&self, string: &str) -> Option<&Index> {
+        self.strings.string_to_string_index.get(string)
+    }
+
+    fn create_new_string(&mut self, string: &str) -> Index {
+        if self.strings.string_to_string_index.contains_key(string) {
+            panic!("Avoid duplication of strings");
+        }
+
+        let id = self.strings.strings.insert(string.into());
+        self.strings
+            .string_to_string_index
+            .insert(string.into(), id);
+        id
+    }
+
+    fn get_or_create_string(&mut self, string: &str) -> (Index, bool) {
+        let mut created = false;
+        let id = self.get_index_from_string(string);
+
+        let id = if let Some(id) = id {
+            *id
+        } else {
+            let id = self.create_new_string(string);
+            created = true;
+            id
+        };
+
+        (id, created)
+    }
+
+    fn create_new_obj_with_existing_string(&mut self, string_index: Index) -> Index {
+        let object = Object::ObjString(ObjectString::new(string_index));
+        self.objects.insert(object)
+    }
+
+    pub fn get_or_create_string_object(&mut self, string: &str) -> Value {
+        //::Object(id, obj)
+        let (string_id, was_string_created) = self.get_or_create_string(string);
+
+        let obj_index = if was_string_created {
+            self.create_new_obj_with_existing_string(string_id)
+        } else {
+            let id = self.strings.string_to_string_obj.get(string);
+            *id.unwrap()
+        };
+
+        if was_string_created {
+            self.strings
+                .string_to_string_obj
+                .insert(string.into(), obj_index);
+        }
+
+        let obj_string = ObjectString::new(string_id);
+        let obj = Object::ObjString(obj_string);
+        Value::Object(obj_index, obj)
+    }
+
+    pub fn new_list(&mut self, elements: Vec<Value>) -> Value {
+        let list = ObjectList::new(self.lists.insert(elements));
+        let object = Object::ObjList(list);
+        Value::Object(self.objects.insert(object.clone()), object)
+    }
+
+    pub fn list(&self, list: &ObjectList) -> &Vec<Value> {
+        self.lists.get(*list.id()).unwrap()
+    }
+
+    pub fn list_mut(&mut self, list: &ObjectList) -> &mut Vec<Value> {
+        self.lists.get_mut(*list.id()).unwrap()
+    }
+
+    pub fn new_map(&mut self, map: Map) -> Value {
+        let map = ObjectMap::new(self.maps.insert(map));
+        let object = Object::ObjMap(map);
+        Value::Object(self.objects.insert(object.clone()), object)
+    }
+
+    pub fn map(&self, map: &ObjectMap) -> &Map {
+        self.maps.get(*map.id()).unwrap()
+    }
+
+    pub fn map_mut(&mut self, map: &ObjectMap) -> &mut Map {
+        self.maps.get_mut(*map.id()).unwrap()
+    }
+
+    pub fn new_range(&mut self, range: Range) -> Value {
+        let range = ObjectRange::new(self.ranges.insert(range));
+        let object = Object::ObjRange(range);
+        Value::Object(self.objects.insert(object.clone()), object)
+    }
+
+    pub fn range(&self, range: &ObjectRange) -> &Range {
+        self.ranges.get(*range.id()).unwrap()
+    }
+
+    pub fn string(&self, string: &ObjectString) -> &str {
+        self.get_string_from_index(string.id())
+    }
+
+    // Registers a method of a built-in type, callable as
+    // `receiver.name(args)`.
+    pub fn define_method(
+        &mut self,
+        receiver: Receiver,
+        name: &'static str,
+        arity: Arity,
+        function: NativeFn,
+    ) {
+        self.natives.push(Native {
+            name: name,
+            arity: arity,
+            function: function,
+        });
+        self.methods
+            .insert((receiver, name.into()), self.natives.len() - 1);
+    }
+
+    // Registers a property of a built-in type, read as `receiver.name`.
+    // `function` gets the receiver as its only argument.
+    pub fn define_property(&mut self, receiver: Receiver, name: &'static str, function: NativeFn) {
+        self.natives.push(Native {
+            name: name,
+            arity: Arity::Exact(0),
+            function: function,
+        });
+        self.properties
+            .insert((receiver, name.into()), self.natives.len() - 1);
+    }
+
+    // Registers a native function as a global of the same name.
+    pub fn define_native(&mut self, name: &'static str, arity: Arity, function: NativeFn) {
+        let value = self.new_native(name, arity, function);
+        self.define_global(name, value, false);
+    }
+
+    // Creates an empty module and binds it to a constant global of the same
+    // name, so scripts can't replace it.
+    pub fn define_module(&mut self, name: &'static str) -> ObjectModule {
+        let module = ObjectModule::new(self.modules.insert(Module::new(name)));
+        let object = Object::ObjModule(module.clone());
+        let value = Value::Object(self.objects.insert(object.clone()), object);
+        self.define_global(name, value, true);
+        module
+    }
+
+    // Registers a native function as a member of a module, called as
+    // `module.name(args)`.
+    pub fn define_module_native(
+        &mut self,
+        module: &ObjectModule,
+        name: &'static str,
+        arity: Arity,
+        function: NativeFn,
+    ) {
+        let value = self.new_native(name, arity, function);
+        self.define_module_value(module, name, value);
+    }
+
+    pub fn define_module_value(&mut self, module: &ObjectModule, name: &str, value: Value) {
+        self.modules.get_mut(*module.id()).unwrap().insert(name, value);
+    }
+
+    fn new_native(&mut self, name: &'static str, arity: Arity, function: NativeFn) -> Value {
+        self.natives.push(Native {
+            name: name,
+            arity: arity,
+            function: function,
+        });
+
+        let object = Object::ObjNative(ObjectNative::new(self.natives.len() - 1));
+        Value::Object(self.objects.insert(object.clone()), object)
+    }
+
+    fn define_global(&mut self, name: &str, value: Value, constant: bool) {
+        self.globals.insert(
+            name.into(),
+            Global {
+                value: value,
+                constant: constant,
+            },
+        );
+    }
+
+    #[allow(dead_code)]
+    pub fn remove_string(&mut self, string: &str) {
+        let id = self
+            .strings
+            .string_to_string_index
+            .get(string)
+            .unwrap();
+        self.strings.strings.remove(*id);
+        self.strings.string_to_string_obj.remove_entry(string);
+
+        let _ = self
+            .strings
+            .string_to_string_index
+            .remove_entry(string);
+    }
+
+    pub fn format_object(&self, id: &Index, o: &Object) -> String {
+        self.format_nested(id, o, &mut vec![])
+    }
+
+    // `enclosing` holds the lists and maps being formatted around this one,
+    // so a container that holds itself prints as `[...]` or `{...}` instead
+    // of recursing forever.
+    fn format_nested(&self, id: &Index, o: &Object, enclosing: &mut Vec<Index>) -> String {
+        let format_element = |element: &Value, enclosing: &mut Vec<Index>| match element {
+            Value::Object(id, o) => self.format_nested(id, o, enclosing),
+            _ => format_value(element, self),
+        };
+
+        match o {
+            Object::ObjString(a) => self.get_string_from_index(a.id()).clone(),
+            Object::ObjList(_) if enclosing.contains(id) => "[...]".into(),
+            Object::ObjMap(_) if enclosing.contains(id) => "{...}".into(),
+            Object::ObjList(list) => {
+                enclosing.push(*id);
+                let elements: Vec<String> = self
+                    .list(list)
+                    .iter()
+                    .map(|element| format_element(element, enclosing))
+                    .collect();
+                enclosing.pop();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::ObjMap(map) => {
+                enclosing.push(*id);
+                let entries: Vec<String> = self
+                    .map(map)
+                    .entries()
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}: {}",
+                            format_element(key, enclosing),
+                            format_element(value, enclosing)
+                        )
+                    })
+                    .collect();
+                enclosing.pop();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Object::ObjRange(range) => {
+                let range = self.range(range);
+                let operator = if range.inclusive { "..=" } else { ".." };
+                let mut text = format!(
+                    "{}{}{}",
+                    format_value(&Value::Number(range.start), self),
+                    operator,
+                    format_value(&Value::Number(range.end), self)
+                );
+                if range.step != 1.0 {
+                    text.push_str(&format!(" step {}", format_value(&Value::Number(range.step), self)));
+                }
+                text
+            }
+            Object::ObjModule(module) => {
+                format!("<module {}>", self.modules.get(*module.id()).unwrap().name)
+            }
+            Object::ObjNative(native) => format!("<native fn {}>", self.natives[native.id()].name),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn dump_stats(&mut self) {
+        println!("================================================");
+        println!("VM contains {} objects", self.objects.len());
+        for (_, object) in self.objects.iter() {
+            print!("object is {:?}. ", object);
+            match &object {
+                Object::ObjString(str_obj) => {
+                    let id = str_obj.id();
+                    let str = self.strings.strings.get(*id).unwrap();
+                    println!("In particular, object is a string: {}", str);
+                }
+                _ => println!(),
+            }
+        }
+        println!("================================================");
+        println!("VM contains {} strings", self.strings.strings.len());
+        for (_, string) in self.strings.strings.iter() {
+            println!("VM String: {}", string);
+        }
+
+        println!("================================================");
+        println!("VM contains {} globals", self.globals.len());
+        for (key, global) in self.globals.iter() {
+            println!("object {:?} has key {:?}", key, global.value);
+        }
+        println!("================================================");
+
+        self.chunk.disassemble_chunk();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    // A sink the test can still read after the VM has taken its clone.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn run(source: &str) -> (InterpretResult, String, String) {
+        let output = SharedBuffer::default();
+        let error_output = SharedBuffer::default();
+        let mut vm = VM::with_output(
+            Chunk::new(),
+            Box::new(output.clone()),
+            Box::new(error_output.clone()),
+        );
+        let result = vm.interpret(source);
+        (result, output.contents(), error_output.contents())
+    }
+
+    #[test]
+    fn print_writes_to_output() {
+        let (result, output, error_output) = run("print 1 + 2;\nprint \"done\";");
+        assert_eq!(result, InterpretResult::InterpretOk);
+        assert_eq!(output, "3\ndone\n");
+        assert_eq!(error_output, "");
+    }
+
+    #[test]
+    fn runtime_error_writes_to_error_output() {
+        let (result, output, error_output) = run("print \"before\";\nprint -nil;");
+        assert_eq!(result, InterpretResult::InterpretRuntimeError);
+        assert_eq!(output, "before\n");
+        assert!(error_output.starts_with("Operand must be a number.\n[line 2] in script\n"));
+    }
+
+    #[test]
+    fn compile_error_writes_to_error_output() {
+        let (result, output, error_output) = run("print 1;\nprint;");
+        assert_eq!(result, InterpretResult::InterpretCompileError);
+        assert_eq!(output, "");
+        assert!(error_output.starts_with("[line 2] Error at ';': Expect expression.\n"));
+    }
+}
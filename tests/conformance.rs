@@ -0,0 +1,287 @@
+// Runs every `.lox` script under `examples/` and `tests/` and checks the
+// interpreter against the expectations written in the script's comments,
+// following the conventions of the Crafting Interpreters test suite:
+//
+//   print 1; // expect: 1
+//   print a; // expect runtime error: Undefined variable 'a'.
+//   var;     // Error at ';': Expect variable name.
+//   // [line 3] Error at end: Expect expression.
+//
+// Three additions: `// args: ` lists command-line flags to run the script
+// with, `// stdin: ` feeds a line to the script's standard input, and
+// `// Warning ...` expects a warning on the current line.
+//
+// `$SCRATCH_DIR` in a script is replaced by a directory the script may
+// write files to. The runner removes it after the script has run.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+const EXIT_OK: i32 = 0;
+const EXIT_COMPILE_ERROR: i32 = 65;
+const EXIT_RUNTIME_ERROR: i32 = 70;
+
+// Directories whose scripts need language features the VM does not
+// support yet (functions, closures and classes).
+const SKIPPED: &[&str] = &["examples/class", "examples/functions"];
+
+// Scripts that only pass when an optional Cargo feature is enabled, with
+// whether that feature is.
+const FEATURE_GATED: &[(&str, bool)] = &[(
+    "tests/unicode/identifiers.lox",
+    cfg!(feature = "unicode-identifiers"),
+)];
+
+const EXPECT_OUTPUT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+const EXPECT_ERROR: &str = "// Error";
+const EXPECT_ERROR_AT_LINE: &str = "// [line ";
+const EXPECT_WARNING: &str = "// Warning";
+const ARGS: &str = "// args: ";
+const STDIN: &str = "// stdin: ";
+const SCRATCH_DIR: &str = "$SCRATCH_DIR";
+
+struct Expectations {
+    args: Vec<String>,
+    stdin: String,
+    output: Vec<(usize, String)>,
+    warnings: Vec<String>,
+    compile_errors: Vec<String>,
+    runtime_error: Option<(usize, String)>,
+    exit_code: i32,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Expectations {
+        let mut expectations = Expectations {
+            args: vec![],
+            stdin: String::new(),
+            output: vec![],
+            warnings: vec![],
+            compile_errors: vec![],
+            runtime_error: None,
+            exit_code: EXIT_OK,
+        };
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+
+            if let Some(position) = line.find(EXPECT_OUTPUT) {
+                let expected = &line[position + EXPECT_OUTPUT.len()..];
+                expectations.output.push((line_number, expected.into()));
+            } else if let Some(position) = line.find(EXPECT_RUNTIME_ERROR) {
+                let expected = &line[position + EXPECT_RUNTIME_ERROR.len()..];
+                expectations.runtime_error = Some((line_number, expected.into()));
+                expectations.exit_code = EXIT_RUNTIME_ERROR;
+            } else if let Some(position) = line.find(EXPECT_ERROR) {
+                let expected = &line[position + "// ".len()..];
+                expectations
+                    .compile_errors
+                    .push(format!("[line {}] {}", line_number, expected));
+                expectations.exit_code = EXIT_COMPILE_ERROR;
+            } else if let Some(position) = line.find(EXPECT_WARNING) {
+                let expected = &line[position + "// ".len()..];
+                expectations
+                    .warnings
+                    .push(format!("[line {}] {}", line_number, expected));
+            } else if let Some(position) = line.find(ARGS) {
+                let args = &line[position + ARGS.len()..];
+                expectations
+                    .args
+                    .extend(args.split_whitespace().map(String::from));
+            } else if let Some(position) = line.find(STDIN) {
+                expectations.stdin.push_str(&line[position + STDIN.len()..]);
+                expectations.stdin.push('\n');
+            } else if let Some(position) = line.find(EXPECT_ERROR_AT_LINE) {
+                let expected = &line[position + "// ".len()..];
+                expectations.compile_errors.push(expected.into());
+                expectations.exit_code = EXIT_COMPILE_ERROR;
+            }
+        }
+
+        expectations
+    }
+}
+
+fn collect_scripts(directory: &Path, scripts: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        let relative = path.strip_prefix(env!("CARGO_MANIFEST_DIR")).unwrap();
+        if SKIPPED.iter().any(|skipped| relative.starts_with(skipped)) {
+            continue;
+        }
+        if FEATURE_GATED
+            .iter()
+            .any(|(gated, enabled)| relative.starts_with(gated) && !enabled)
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            scripts.push(path);
+        }
+    }
+}
+
+fn run_script(path: &Path) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expectations = Expectations::parse(&source);
+
+    // Scripts that use a scratch directory run from a copy with its path
+    // filled in. The copy lives in the directory, so it goes away too.
+    let relative = path.strip_prefix(env!("CARGO_MANIFEST_DIR")).unwrap();
+    let scratch_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(relative.with_extension(""));
+    let mut script = path.to_path_buf();
+    if source.contains(SCRATCH_DIR) {
+        fs::create_dir_all(&scratch_dir).unwrap();
+        script = scratch_dir.join(path.file_name().unwrap());
+        let scratch_path = scratch_dir.display().to_string().replace('\\', "/");
+        fs::write(&script, source.replace(SCRATCH_DIR, &scratch_path)).unwrap();
+    }
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust_lox"))
+        .args(&expectations.args)
+        .arg(&script)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Dropping the handle closes stdin, so the script sees the end of the
+    // input after the last line.
+    let mut stdin = child.stdin.take().unwrap();
+    let _ = stdin.write_all(expectations.stdin.as_bytes());
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    if script != path {
+        fs::remove_dir_all(&scratch_dir).unwrap();
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout_lines: Vec<&str> = stdout.lines().collect();
+    // Indented lines are source excerpts rendered under a diagnostic.
+    let (warning_lines, stderr_lines): (Vec<&str>, Vec<&str>) = stderr
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .partition(|line| line.starts_with("[line ") && line.contains("] Warning"));
+
+    let mut failures = vec![];
+
+    for expected in &expectations.warnings {
+        if !warning_lines.contains(&expected.as_str()) {
+            failures.push(format!("Missing expected warning: {}", expected));
+        }
+    }
+    for actual in &warning_lines {
+        if !expectations.warnings.iter().any(|w| w == actual) {
+            failures.push(format!("Unexpected warning: {}", actual));
+        }
+    }
+
+    if !expectations.compile_errors.is_empty() {
+        for expected in &expectations.compile_errors {
+            if !stderr_lines.contains(&expected.as_str()) {
+                failures.push(format!("Missing expected error: {}", expected));
+            }
+        }
+        for actual in &stderr_lines {
+            if !expectations.compile_errors.iter().any(|e| e == actual) {
+                failures.push(format!("Unexpected error: {}", actual));
+            }
+        }
+    } else if let Some((line, message)) = &expectations.runtime_error {
+        match stderr_lines.first() {
+            Some(actual) if actual == message => {
+                let trace = format!("[line {}]", line);
+                if !stderr_lines.iter().skip(1).any(|l| l.starts_with(&trace)) {
+                    failures.push(format!("Expected stack trace starting with {}", trace));
+                }
+            }
+            Some(actual) => failures.push(format!(
+                "Expected runtime error '{}' and got: {}",
+                message, actual
+            )),
+            None => failures.push(format!(
+                "Expected runtime error '{}' and got none.",
+                message
+            )),
+        }
+    } else {
+        for actual in &stderr_lines {
+            failures.push(format!("Unexpected output on stderr: {}", actual));
+        }
+    }
+
+    for (index, (line, expected)) in expectations.output.iter().enumerate() {
+        match stdout_lines.get(index) {
+            Some(actual) if actual == expected => {}
+            Some(actual) => failures.push(format!(
+                "Expected output '{}' on line {} and got '{}'.",
+                expected, line, actual
+            )),
+            None => failures.push(format!(
+                "Missing expected output '{}' on line {}.",
+                expected, line
+            )),
+        }
+    }
+    for actual in stdout_lines.iter().skip(expectations.output.len()) {
+        failures.push(format!("Got output '{}' when none was expected.", actual));
+    }
+
+    let exit_code = output.status.code().unwrap_or(-1);
+    if exit_code != expectations.exit_code {
+        failures.push(format!(
+            "Expected return code {} and got {}.",
+            expectations.exit_code, exit_code
+        ));
+    }
+
+    failures
+}
+
+#[test]
+fn conformance() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut scripts = vec![];
+    collect_scripts(&root.join("examples"), &mut scripts);
+    collect_scripts(&root.join("tests"), &mut scripts);
+
+    let mut report = String::new();
+    let mut failed = 0;
+
+    for script in &scripts {
+        let failures = run_script(script);
+        if failures.is_empty() {
+            continue;
+        }
+
+        failed += 1;
+        let relative = script.strip_prefix(root).unwrap();
+        report.push_str(&format!("FAIL {}\n", relative.display()));
+        for failure in failures {
+            report.push_str(&format!("     {}\n", failure));
+        }
+    }
+
+    if failed > 0 {
+        panic!(
+            "{} of {} scripts failed:\n{}",
+            failed,
+            scripts.len(),
+            report
+        );
+    }
+}